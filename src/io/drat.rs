@@ -2,7 +2,7 @@ use std::io::{BufWriter, Write};
 
 use crate::types::Proof;
 
-#[derive(Clone, clap::ValueEnum)]
+#[derive(Clone, Copy, clap::ValueEnum)]
 pub enum Format {
     Binary,
     Plain,
@@ -2,8 +2,11 @@ pub mod drat;
 
 use std::io::{BufRead, BufReader, BufWriter, Read, Write};
 
-use crate::types::{Lit, Problem, Solution};
+use crate::types::{Clause, Lit, Problem, Solution};
 
+/// Reads a problem in DIMACS CNF format, extended by XOR constraints
+/// on lines starting with `x` as in CryptoMiniSat, e.g., `x1 -2 3 0`.
+/// They are counted among the clauses in the problem line.
 pub fn read_problem(reader: &mut impl Read) -> Problem {
     let mut lines = BufReader::new(reader).lines().map(|l| l.unwrap());
 
@@ -27,6 +30,7 @@ pub fn read_problem(reader: &mut impl Read) -> Problem {
 
     let mut clauses = vec![];
     let mut clause = vec![];
+    let mut xors = vec![];
 
     for line in lines {
         // FIXME: doesn't conform to the standard format
@@ -34,6 +38,17 @@ pub fn read_problem(reader: &mut impl Read) -> Problem {
             break;
         }
 
+        if let Some(rest) = line.strip_prefix('x') {
+            // the whole constraint is on a single line
+            let mut xor: Clause = rest
+                .split_whitespace()
+                .map(|word| word.parse::<Lit>().unwrap())
+                .collect();
+            assert_eq!(xor.pop(), Some(0));
+            xors.push(xor);
+            continue;
+        }
+
         for word in line.split_whitespace() {
             let lit = word.parse::<Lit>().unwrap();
             match lit {
@@ -48,14 +63,19 @@ pub fn read_problem(reader: &mut impl Read) -> Problem {
         }
     }
 
-    assert_eq!(clause_count, clauses.len());
+    assert_eq!(clause_count, clauses.len() + xors.len());
     assert!(clauses
         .iter()
+        .chain(&xors)
         .flatten()
         .map(|lit| lit.unsigned_abs() as usize)
         .all(|var| (1..=var_count).contains(&var)));
 
-    Problem { var_count, clauses }
+    Problem {
+        var_count,
+        clauses,
+        xors,
+    }
 }
 
 pub fn write_solution(writer: &mut impl Write, solution: &Solution) {
@@ -88,7 +108,9 @@ mod tests {
     #[test]
     fn basic() {
         let input = b"c whatever\np cnf 2 2\n1 2 0\n1 -2 0";
-        let Problem { var_count, clauses } = read_problem(&mut input.as_slice());
+        let Problem {
+            var_count, clauses, ..
+        } = read_problem(&mut input.as_slice());
         assert_eq!(var_count, 2);
         assert_eq!(clauses.len(), 2);
         assert_eq!(clauses[0], vec![1, 2]);
@@ -102,4 +124,12 @@ mod tests {
         assert_eq!(clauses.len(), 1);
         assert_eq!(clauses[0], vec![1, 1, -1, -1]);
     }
+
+    #[test]
+    fn xor() {
+        let input = b"p cnf 3 3\nx1 -2 0\n1 2 0\nx 2 3 -1 0\n";
+        let Problem { clauses, xors, .. } = read_problem(&mut input.as_slice());
+        assert_eq!(clauses, vec![vec![1, 2]]);
+        assert_eq!(xors, vec![vec![1, -2], vec![2, 3, -1]]);
+    }
 }
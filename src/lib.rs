@@ -1,4 +1,5 @@
 pub mod io;
 pub mod parallel;
+pub mod preprocess;
 pub mod solver;
 pub mod types;
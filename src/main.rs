@@ -1,5 +1,5 @@
-use clap::Parser;
-use vw_passat::{io, parallel, solver};
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use vw_passat::{io, parallel, preprocess::Preprocessor, solver};
 
 #[derive(Parser)]
 #[command(about = "A CDCL-based SAT solver.")]
@@ -12,7 +12,28 @@ struct Args {
     #[arg(short, long, value_name = "N")]
     jobs: Option<usize>,
 
-    /// Generate a DRAT proof
+    /// Seed for randomized decisions and initial perturbation
+    /// of the variable order and phases
+    #[arg(short, long)]
+    seed: Option<u64>,
+    /// Probability of a random decision
+    #[arg(long, value_name = "P", default_value_t = 0.0, value_parser = probability)]
+    random_freq: f64,
+    /// Propagate cardinality constraints detected in the clauses natively
+    #[arg(long)]
+    cardinality: bool,
+    /// Propagate XOR constraints through their clauses only,
+    /// skipping their detection and Gauss-Jordan elimination
+    #[arg(long)]
+    no_gauss: bool,
+
+    /// Skip preprocessing (substitution, subsumption, variable addition,
+    /// variable and blocked clause elimination)
+    #[arg(long)]
+    no_preprocess: bool,
+
+    /// Generate a DRAT proof,
+    /// not supported for inputs with XOR constraints
     #[arg(short, long, value_name = "PATH")]
     proof: Option<String>,
     /// Format of the generated proof
@@ -20,6 +41,15 @@ struct Args {
     pformat: io::drat::Format,
 }
 
+fn probability(arg: &str) -> Result<f64, String> {
+    let p: f64 = arg.parse().map_err(|err| format!("{err}"))?;
+    if (0.0..=1.0).contains(&p) {
+        Ok(p)
+    } else {
+        Err("must be between 0 and 1".to_string())
+    }
+}
+
 fn main() {
     let args = Args::parse();
 
@@ -31,18 +61,58 @@ fn main() {
     let mut input = std::fs::File::open(args.input).unwrap();
     let mut output = std::io::stdout();
 
-    let problem = io::read_problem(&mut input);
-    match args.proof {
-        None => {
-            let solution = parallel::solve(problem, args.jobs);
-            io::write_solution(&mut output, &solution);
-        }
+    let options = solver::Options {
+        seed: args.seed,
+        random_freq: args.random_freq,
+        cardinality: args.cardinality,
+        no_gauss: args.no_gauss,
+    };
+
+    let mut problem = io::read_problem(&mut input);
+    if args.proof.is_some() && !problem.xors.is_empty() {
+        // the proof would refer to the clausal encoding of the XORs,
+        // which no checker gets to see
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "proof generation is not supported for inputs with XOR constraints",
+            )
+            .exit();
+    }
+    let mut preprocessor = None;
+    if !args.no_preprocess {
+        let mut pre = match args.proof {
+            None => Preprocessor::new(problem),
+            Some(_) => Preprocessor::with_proof(problem),
+        };
+        pre.substitute();
+        pre.subsume();
+        pre.add_variables();
+        pre.eliminate();
+        pre.eliminate_blocked();
+        problem = pre.problem();
+        preprocessor = Some(pre);
+    }
+
+    let solution = match args.proof {
+        None => parallel::solve(problem, args.jobs, &options),
         Some(path) => {
             let mut proof = std::fs::File::create(path).unwrap();
             let mut solver = solver::Solver::with_proof(problem);
+            solver.set_options(&options);
             let solution = solver.solve();
+            // the solver continues from the preprocessed clauses
+            if let Some(pre_proof) = preprocessor.as_ref().and_then(Preprocessor::proof) {
+                io::drat::write_proof(&mut proof, args.pformat, pre_proof);
+            }
             io::drat::write_proof(&mut proof, args.pformat, solver.proof().unwrap());
-            io::write_solution(&mut output, &solution);
+            solution
         }
     };
+
+    let solution = match &preprocessor {
+        Some(pre) => pre.reconstruct(solution),
+        None => solution,
+    };
+    io::write_solution(&mut output, &solution);
 }
@@ -1,7 +1,7 @@
 use std::{collections::BinaryHeap, iter::zip, sync::mpsc::channel, thread};
 
 use crate::{
-    solver::Solver,
+    solver::{Options, Solver},
     types::{to_var, Lit, Problem, Solution, Var},
 };
 
@@ -77,7 +77,10 @@ fn split(problem: Problem, n: usize) -> Vec<Problem> {
     subproblems
 }
 
-pub fn solve(problem: Problem, n: Option<usize>) -> Solution {
+/// Solves `problem` using `n` threads. If there is more than one thread
+/// (or a seed is given), each thread is seeded differently
+/// to diversify the search.
+pub fn solve(problem: Problem, n: Option<usize>, options: &Options) -> Solution {
     let n = n.unwrap_or(
         thread::available_parallelism()
             .map(|val| val.get())
@@ -88,10 +91,18 @@ pub fn solve(problem: Problem, n: Option<usize>) -> Solution {
 
     let (tx, rx) = channel::<Solution>();
 
-    for subproblem in subproblems {
+    for (i, subproblem) in subproblems.into_iter().enumerate() {
         let thread_tx = tx.clone();
+        let options = Options {
+            seed: options
+                .seed
+                .or((n > 1).then_some(0))
+                .map(|seed| seed.wrapping_add(i as u64)),
+            ..options.clone()
+        };
         thread::spawn(move || {
             let mut solver = Solver::new(subproblem);
+            solver.set_options(&options);
             let solution = solver.solve();
             let _ = thread_tx.send(solution);
         });
@@ -0,0 +1,137 @@
+use crate::{
+    solver::map::LitMap,
+    types::{to_var, Lit},
+};
+
+use super::Preprocessor;
+
+/// Literals whose negation occurs more often are not checked.
+const OCCURRENCE_LIMIT: usize = 100;
+
+impl Preprocessor {
+    /// Blocked clause elimination. A clause is blocked by its literal `lit`
+    /// if all its resolvents on `lit` are tautologies. Such a clause can be
+    /// removed, models are repaired by setting `lit` if the clause
+    /// is falsified.
+    pub fn eliminate_blocked(&mut self) {
+        if self.unsat {
+            return;
+        }
+
+        let mut queue: Vec<Lit> = (1..=self.var_count as Lit)
+            .flat_map(|var| [var, -var])
+            .collect();
+        let mut queued = LitMap::<bool>::new(self.var_count);
+        for &lit in &queue {
+            queued[lit] = true;
+        }
+        let mut marks = LitMap::<bool>::new(self.var_count);
+
+        while let Some(lit) = queue.pop() {
+            queued[lit] = false;
+            let var = to_var(lit);
+            if self.frozen[var]
+                || self.eliminated[var]
+                || self.values[var].is_some()
+                || self.occurs[-lit].len() > OCCURRENCE_LIMIT
+            {
+                continue;
+            }
+
+            for i in self.occurs[lit].clone() {
+                if !self.is_blocked(i, lit, &mut marks) {
+                    continue;
+                }
+
+                let clause = self.remove(i);
+                // clauses blocked by the negations might be blocked now
+                for &other in &clause {
+                    if !queued[-other] {
+                        queued[-other] = true;
+                        queue.push(-other);
+                    }
+                }
+                self.stack.push((lit, clause));
+            }
+        }
+    }
+
+    /// Whether the `i`-th clause is blocked by its literal `lit`.
+    fn is_blocked(&self, i: usize, lit: Lit, marks: &mut LitMap<bool>) -> bool {
+        let clause = self.clauses[i].as_ref().unwrap();
+        for &other in clause {
+            marks[other] = true;
+        }
+        let blocked = self.occurs[-lit].iter().all(|&j| {
+            self.clauses[j]
+                .as_ref()
+                .unwrap()
+                .iter()
+                .any(|&other| other != -lit && marks[-other])
+        });
+        for &other in clause {
+            marks[other] = false;
+        }
+        blocked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        preprocess::{
+            tests::{brute_force, random_problem, satisfies},
+            Preprocessor,
+        },
+        solver::random::Rng,
+        types::{Problem, Solution},
+    };
+
+    #[test]
+    fn blocked() {
+        let problem = Problem {
+            var_count: 3,
+            // the clauses with 1 and -1 block each other
+            clauses: vec![vec![1, 2], vec![-1, -2], vec![2, 3], vec![-2, -3]],
+            xors: vec![],
+        };
+        let mut preprocessor = Preprocessor::new(problem.clone());
+        preprocessor.freeze(2);
+        preprocessor.freeze(3);
+        preprocessor.eliminate_blocked();
+
+        let simplified = preprocessor.problem();
+        assert_eq!(simplified.clauses, vec![vec![2, 3], vec![-3, -2]]);
+        let solution = preprocessor.reconstruct(Solution::Sat {
+            model: vec![1, 2, -3],
+        });
+        let Solution::Sat { model } = solution else {
+            panic!("expected a model");
+        };
+        assert_eq!(model, vec![-1, 2, -3]);
+        assert!(satisfies(&model, &problem.clauses));
+    }
+
+    #[test]
+    fn random() {
+        let mut rng = Rng::new(1);
+        for _ in 0..200 {
+            let problem = random_problem(&mut rng);
+
+            let mut preprocessor = Preprocessor::new(problem.clone());
+            preprocessor.eliminate_blocked();
+            let simplified = preprocessor.problem();
+
+            match brute_force(&simplified) {
+                Some(model) => {
+                    let solution = preprocessor.reconstruct(Solution::Sat { model });
+                    let Solution::Sat { model } = solution else {
+                        panic!("expected a model");
+                    };
+                    assert!(satisfies(&model, &problem.clauses));
+                }
+                None => assert!(brute_force(&problem).is_none()),
+            }
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::BinaryHeap;
+
+use crate::{
+    solver::map::LitMap,
+    types::{Clause, Lit, ProofStep, Var},
+};
+
+use super::Preprocessor;
+
+/// The number of occurrences visited while matching grid rows, per clause.
+const BVA_EFFORT: usize = 100;
+
+impl Preprocessor {
+    /// Bounded variable addition as in SimpleBVA. A grid of clauses
+    /// `lit ∨ C` for all `lit` in `L` and `C` in `M`, e.g., a pairwise
+    /// at-most-one constraint, is replaced by `-x ∨ lit` for each `lit`
+    /// and `x ∨ C` for each `C` with a fresh variable `x`, as long as that
+    /// reduces the number of clauses. The literals with the most
+    /// occurrences are tried first until the effort is exhausted.
+    pub fn add_variables(&mut self) {
+        if self.unsat {
+            return;
+        }
+
+        let mut queue: BinaryHeap<(usize, Lit)> = (1..=self.var_count as Lit)
+            .flat_map(|var| [var, -var])
+            .map(|lit| (self.occurs[lit].len(), lit))
+            .collect();
+        let mut marks = LitMap::<bool>::new(self.var_count);
+        let mut counts = LitMap::<usize>::new(self.var_count);
+        let budget = BVA_EFFORT * self.clauses.iter().flatten().count();
+        let mut effort = 0;
+
+        while let Some((count, lit)) = queue.pop() {
+            if effort > budget {
+                break;
+            }
+            let current = self.occurs[lit].len();
+            if current != count {
+                // outdated entry
+                queue.push((current, lit));
+                continue;
+            }
+            // at least three clauses per literal are needed for a reduction
+            if current < 3 {
+                continue;
+            }
+
+            let Some((lits, grid)) = self.find_grid(lit, &mut marks, &mut counts, &mut effort)
+            else {
+                continue;
+            };
+            let var = self.new_var();
+            marks.grow(self.var_count);
+            counts.grow(self.var_count);
+            self.replace_grid(lit, &lits, &grid, var);
+
+            let x = var as Lit;
+            for other in lits.into_iter().chain([x, -x]) {
+                queue.push((self.occurs[other].len(), other));
+            }
+        }
+    }
+
+    /// Finds the literals `L` (starting with `lit`) and the rows of the
+    /// largest reducible grid found greedily. Each row consists of the
+    /// indices of the clauses `other ∨ C` for all `other` in `L`.
+    fn find_grid(
+        &self,
+        lit: Lit,
+        marks: &mut LitMap<bool>,
+        counts: &mut LitMap<usize>,
+        effort: &mut usize,
+    ) -> Option<(Vec<Lit>, Vec<Vec<usize>>)> {
+        // duplicate rows would share their clauses, substitution may have
+        // created some
+        let mut rows = self.occurs[lit].clone();
+        rows.sort_unstable_by(|&i, &j| self.clauses[i].cmp(&self.clauses[j]));
+        rows.dedup_by(|i, j| self.clauses[*i] == self.clauses[*j]);
+
+        // The literals `other` with `other ∨ C` for each row `lit ∨ C`,
+        // sorted, together with the index of that clause. They only depend
+        // on the row, so they are found once and rows are dropped from the
+        // grid as it gets narrower.
+        let mut matches: Vec<Vec<(Lit, usize)>> = vec![];
+        for &i in &rows {
+            let clause = self.clauses[i].as_ref().unwrap();
+            let mut row = vec![];
+            if let Some(&pivot) = clause
+                .iter()
+                .filter(|&&other| other != lit)
+                .min_by_key(|&&other| self.occurs[other].len())
+            {
+                for &other in clause {
+                    marks[other] = true;
+                }
+                *effort += self.occurs[pivot].len();
+                for &j in &self.occurs[pivot] {
+                    let candidate = self.clauses[j].as_ref().unwrap();
+                    if j == i || candidate.len() != clause.len() || candidate.contains(&lit) {
+                        continue;
+                    }
+                    // the candidate is the clause with `lit` replaced
+                    let mut unmarked = candidate.iter().filter(|&&other| !marks[other]);
+                    if let (Some(&other), None) = (unmarked.next(), unmarked.next()) {
+                        if other != -lit {
+                            row.push((other, j));
+                        }
+                    }
+                }
+                for &other in clause {
+                    marks[other] = false;
+                }
+            }
+            row.sort_unstable();
+            row.dedup_by_key(|&mut (other, _)| other);
+            matches.push(row);
+        }
+
+        // the number of rows in the grid matching each literal not in `L`
+        let mut candidates = vec![];
+        for &(other, _) in matches.iter().flatten() {
+            if counts[other] == 0 {
+                candidates.push(other);
+            }
+            counts[other] += 1;
+        }
+        let mut in_grid = vec![true; rows.len()];
+        let mut size = rows.len();
+        let mut lits = vec![lit];
+
+        while let Some((count, best)) = candidates
+            .iter()
+            .filter(|&&other| !marks[other])
+            .map(|&other| (counts[other], other))
+            .max()
+        {
+            if reduction(lits.len() + 1, count) <= reduction(lits.len(), size) {
+                break;
+            }
+
+            marks[best] = true;
+            lits.push(best);
+            for (row, kept) in matches.iter().zip(&mut in_grid) {
+                if *kept
+                    && row
+                        .binary_search_by_key(&best, |&(other, _)| other)
+                        .is_err()
+                {
+                    *kept = false;
+                    size -= 1;
+                    for &(other, _) in row {
+                        counts[other] -= 1;
+                    }
+                }
+            }
+        }
+        for &other in &lits {
+            marks[other] = false;
+        }
+        for other in candidates {
+            counts[other] = 0;
+        }
+
+        if reduction(lits.len(), size) <= 0 {
+            return None;
+        }
+        let grid = rows
+            .into_iter()
+            .zip(matches)
+            .zip(in_grid)
+            .filter(|&(_, in_grid)| in_grid)
+            .map(|((i, row), _)| {
+                let mut clauses = vec![i];
+                for &other in &lits[1..] {
+                    let k = row.binary_search_by_key(&other, |&(other, _)| other);
+                    clauses.push(row[k.unwrap()].1);
+                }
+                clauses
+            })
+            .collect();
+        Some((lits, grid))
+    }
+
+    fn replace_grid(&mut self, lit: Lit, lits: &[Lit], grid: &[Vec<usize>], var: Var) {
+        let x = var as Lit;
+
+        // the new clauses are RAT on the fresh literal, which must be first
+        // in the proof
+        for &other in lits {
+            self.add_extension(vec![-x, other]);
+        }
+        for row in grid {
+            let mut clause = vec![x];
+            clause.extend(
+                self.clauses[row[0]]
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .filter(|&&other| other != lit),
+            );
+            self.add_extension(clause);
+        }
+
+        // the occurrences of the literals in `L` are cleaned up once
+        // instead of for each removed clause
+        let mut touched = vec![];
+        for &i in grid.iter().flatten() {
+            let clause = self.clauses[i].take().unwrap();
+            touched.extend_from_slice(&clause);
+            if let Some(proof) = self.proof.as_mut() {
+                proof.push((ProofStep::Delete, clause));
+            }
+        }
+        touched.sort_unstable();
+        touched.dedup();
+        for other in touched {
+            let clauses = &self.clauses;
+            self.occurs[other].retain(|&i| clauses[i].is_some());
+        }
+    }
+
+    /// Adds a clause whose first literal is fresh.
+    fn add_extension(&mut self, mut clause: Clause) {
+        if let Some(proof) = self.proof.as_mut() {
+            proof.push((ProofStep::Add, clause.clone()));
+        }
+        clause.sort_unstable();
+        self.add(clause);
+    }
+}
+
+/// The number of clauses saved by replacing a grid of size `lits * rows`.
+fn reduction(lits: usize, rows: usize) -> isize {
+    (lits * rows) as isize - (lits + rows) as isize
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        preprocess::{
+            tests::{brute_force, satisfies},
+            Preprocessor,
+        },
+        types::{Clause, Problem, Solution},
+    };
+
+    #[test]
+    fn at_most_one() {
+        let var_count = 8;
+        let clauses: Vec<Clause> = (1..=var_count)
+            .flat_map(|i| (i + 1..=var_count).map(move |j| vec![-i, -j]))
+            .chain([(1..=var_count).collect()])
+            .collect();
+        let problem = Problem {
+            var_count: var_count as usize,
+            clauses,
+            xors: vec![],
+        };
+
+        let mut preprocessor = Preprocessor::with_proof(problem.clone());
+        preprocessor.add_variables();
+        let simplified = preprocessor.problem();
+        assert!(simplified.var_count > problem.var_count);
+        assert!(simplified.clauses.len() < problem.clauses.len());
+
+        let model = brute_force(&simplified).unwrap();
+        let Solution::Sat { model } = preprocessor.reconstruct(Solution::Sat { model }) else {
+            panic!("expected a model");
+        };
+        assert_eq!(model.len(), problem.var_count);
+        assert!(satisfies(&model, &problem.clauses));
+        assert_eq!(model.iter().filter(|&&lit| lit > 0).count(), 1);
+    }
+
+    #[test]
+    fn duplicates() {
+        let var_count = 6;
+        let clauses: Vec<Clause> = (1..=var_count)
+            .flat_map(|i| (i + 1..=var_count).map(move |j| vec![-i, -j]))
+            .collect();
+        let mut problem = Problem {
+            var_count: var_count as usize,
+            clauses,
+            xors: vec![],
+        };
+
+        // duplicates, as left behind by substitution, in the input
+        // and in the grid
+        let mut preprocessor = Preprocessor::new(problem.clone());
+        preprocessor.add(vec![-2, -1]);
+        problem.clauses.push(vec![-1, -2]);
+        for mut preprocessor in [preprocessor, Preprocessor::new(problem.clone())] {
+            preprocessor.add_variables();
+            let simplified = preprocessor.problem();
+            assert!(simplified.clauses.len() < problem.clauses.len() - 1);
+
+            let model = brute_force(&simplified).unwrap();
+            let Solution::Sat { model } = preprocessor.reconstruct(Solution::Sat { model }) else {
+                panic!("expected a model");
+            };
+            assert!(satisfies(&model, &problem.clauses));
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::types::{to_var, Clause, Lit, Var};
+
+use super::Preprocessor;
+
+/// Variables with more occurrences of both literals are not eliminated.
+const OCCURRENCE_LIMIT: usize = 100;
+/// Variables whose elimination would produce longer resolvents are kept.
+const RESOLVENT_LIMIT: usize = 20;
+
+impl Preprocessor {
+    /// Bounded variable elimination as in SatELite. A variable is eliminated
+    /// by replacing the clauses containing it with their non-tautological
+    /// resolvents, as long as that does not increase the number of clauses.
+    /// Variables with fewer occurrences are tried first.
+    pub fn eliminate(&mut self) {
+        let mut queue: BinaryHeap<Reverse<(usize, Var)>> = (1..=self.var_count)
+            .map(|var| Reverse((self.elim_cost(var), var)))
+            .collect();
+
+        while let Some(Reverse((cost, var))) = queue.pop() {
+            if self.unsat {
+                return;
+            }
+            if self.frozen[var] || self.eliminated[var] || self.values[var].is_some() {
+                continue;
+            }
+            let current = self.elim_cost(var);
+            if current != cost {
+                // outdated entry
+                queue.push(Reverse((current, var)));
+                continue;
+            }
+
+            if let Some(touched) = self.try_eliminate(var) {
+                for other in touched {
+                    queue.push(Reverse((self.elim_cost(other), other)));
+                }
+                self.propagate();
+            }
+        }
+    }
+
+    fn elim_cost(&self, var: Var) -> usize {
+        let lit = var as Lit;
+        self.occurs[lit].len() * self.occurs[-lit].len()
+    }
+
+    /// Eliminates `var` if the bounds allow it and returns the variables
+    /// of the removed clauses.
+    fn try_eliminate(&mut self, var: Var) -> Option<Vec<Var>> {
+        let lit = var as Lit;
+        let pos = self.occurs[lit].clone();
+        let neg = self.occurs[-lit].clone();
+        if pos.len() + neg.len() == 0
+            || (pos.len() > OCCURRENCE_LIMIT && neg.len() > OCCURRENCE_LIMIT)
+        {
+            return None;
+        }
+
+        let mut resolvents = vec![];
+        for &i in &pos {
+            for &j in &neg {
+                let clause_pos = self.clauses[i].as_ref().unwrap();
+                let clause_neg = self.clauses[j].as_ref().unwrap();
+                if let Some(resolvent) = resolve(clause_pos, clause_neg, lit) {
+                    if resolvent.len() > RESOLVENT_LIMIT
+                        || resolvents.len() == pos.len() + neg.len()
+                    {
+                        return None;
+                    }
+                    resolvents.push(resolvent);
+                }
+            }
+        }
+
+        // the resolvents need to be in the proof before their antecedents
+        // are deleted
+        for resolvent in resolvents {
+            self.derive(resolvent);
+        }
+
+        let mut touched = vec![];
+        for i in pos.into_iter().chain(neg) {
+            let clause = self.remove(i);
+            touched.extend(clause.iter().map(|&other| to_var(other)));
+            let pivot = if clause.contains(&lit) { lit } else { -lit };
+            self.stack.push((pivot, clause));
+        }
+        self.eliminated[var] = true;
+
+        touched.sort_unstable();
+        touched.dedup();
+        touched.retain(|&other| other != var);
+        Some(touched)
+    }
+}
+
+/// The resolvent of the sorted clauses on `lit` (contained in `pos`),
+/// or `None` if it is a tautology.
+fn resolve(pos: &[Lit], neg: &[Lit], lit: Lit) -> Option<Clause> {
+    let mut resolvent: Clause = pos.iter().copied().filter(|&other| other != lit).collect();
+    for &other in neg {
+        if other == -lit || pos.contains(&other) {
+            continue;
+        }
+        if pos.contains(&-other) {
+            return None;
+        }
+        resolvent.push(other);
+    }
+    resolvent.sort_unstable();
+    Some(resolvent)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        preprocess::{
+            tests::{brute_force, random_problem, satisfies},
+            Preprocessor,
+        },
+        solver::random::Rng,
+        types::{Problem, Solution},
+    };
+
+    use super::resolve;
+
+    #[test]
+    fn resolvents() {
+        assert_eq!(resolve(&[1, 2, 3], &[-1, 3, 4], 1), Some(vec![2, 3, 4]));
+        assert_eq!(resolve(&[-2, 1], &[-1, 2], 1), None);
+        assert_eq!(resolve(&[-1], &[1], -1), Some(vec![]));
+    }
+
+    #[test]
+    fn eliminate() {
+        let problem = Problem {
+            var_count: 4,
+            clauses: vec![vec![1, 2], vec![-1, 3], vec![-1, 4], vec![-2, -3, -4]],
+            xors: vec![],
+        };
+        let mut preprocessor = Preprocessor::new(problem.clone());
+        preprocessor.freeze(2);
+        preprocessor.eliminate();
+
+        let simplified = preprocessor.problem();
+        assert!(simplified
+            .clauses
+            .iter()
+            .flatten()
+            .all(|&lit| lit.abs() == 2));
+        let model = brute_force(&simplified).unwrap();
+        let Solution::Sat { model } = preprocessor.reconstruct(Solution::Sat { model }) else {
+            panic!("expected a model");
+        };
+        assert!(satisfies(&model, &problem.clauses));
+    }
+
+    #[test]
+    fn random() {
+        let mut rng = Rng::new(0);
+        for _ in 0..200 {
+            let problem = random_problem(&mut rng);
+
+            let mut preprocessor = Preprocessor::new(problem.clone());
+            preprocessor.substitute();
+            preprocessor.subsume();
+            preprocessor.add_variables();
+            preprocessor.eliminate();
+            preprocessor.eliminate_blocked();
+            let simplified = preprocessor.problem();
+
+            match brute_force(&simplified) {
+                Some(model) => {
+                    let solution = preprocessor.reconstruct(Solution::Sat { model });
+                    let Solution::Sat { model } = solution else {
+                        panic!("expected a model");
+                    };
+                    assert!(satisfies(&model, &problem.clauses));
+                }
+                None => assert!(brute_force(&problem).is_none()),
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{
+    solver::map::LitMap,
+    types::{to_var, Clause, Lit, ProofStep},
+};
+
+use super::{is_tautology, Preprocessor};
+
+impl Preprocessor {
+    /// Equivalent literal substitution. Literals in the same strongly
+    /// connected component of the binary implication graph are equivalent,
+    /// so each of them is replaced by a representative of the component,
+    /// the one with the lowest variable (frozen variables are kept).
+    pub fn substitute(&mut self) {
+        if self.unsat {
+            return;
+        }
+
+        let mut repr = LitMap::<Lit>::new(self.var_count);
+        for scc in self.sccs() {
+            if let Some(&lit) = scc.iter().find(|&&lit| scc.contains(&-lit)) {
+                // lit implies -lit and vice versa
+                self.derive(vec![-lit]);
+                self.derive(vec![lit]);
+                return self.propagate();
+            }
+            // the negated component is found as well, it is handled here
+            if scc.iter().any(|&lit| repr[lit] != 0) {
+                continue;
+            }
+            let &rep = scc
+                .iter()
+                .min_by_key(|&&lit| (!self.frozen[to_var(lit)], to_var(lit)))
+                .unwrap();
+            for lit in scc {
+                if lit != rep && !self.frozen[to_var(lit)] {
+                    repr[lit] = rep;
+                    repr[-lit] = -rep;
+                }
+            }
+        }
+
+        let substituted: Vec<Lit> = (1..=self.var_count as Lit)
+            .filter(|&lit| repr[lit] != 0)
+            .collect();
+        if substituted.is_empty() {
+            return;
+        }
+
+        // the equivalences justify the substituted clauses in the proof
+        let equivalences: Vec<Clause> = substituted
+            .iter()
+            .flat_map(|&lit| [vec![-lit, repr[lit]], vec![lit, -repr[lit]]])
+            .collect();
+        if let Some(proof) = self.proof.as_mut() {
+            for clause in &equivalences {
+                proof.push((ProofStep::Add, clause.clone()));
+            }
+        }
+
+        let mut indices: Vec<usize> = substituted
+            .iter()
+            .flat_map(|&lit| self.occurs[lit].iter().chain(&self.occurs[-lit]))
+            .copied()
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        for i in indices {
+            let mut clause: Clause = self.clauses[i]
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|&lit| if repr[lit] != 0 { repr[lit] } else { lit })
+                .collect();
+            clause.sort_unstable();
+            clause.dedup();
+            if !is_tautology(&clause) {
+                self.derive(clause);
+            }
+            self.remove(i);
+        }
+
+        for lit in substituted {
+            self.eliminated[to_var(lit)] = true;
+            self.stack.push((lit, vec![lit, -repr[lit]]));
+            self.stack.push((-lit, vec![-lit, repr[lit]]));
+        }
+        if let Some(proof) = self.proof.as_mut() {
+            for clause in equivalences {
+                proof.push((ProofStep::Delete, clause));
+            }
+        }
+
+        self.propagate();
+    }
+
+    /// The non-trivial strongly connected components of the binary
+    /// implication graph, found by an iterative version of Tarjan's algorithm.
+    fn sccs(&self) -> Vec<Vec<Lit>> {
+        let mut graph = LitMap::<Vec<Lit>>::new(self.var_count);
+        for clause in self.clauses.iter().flatten() {
+            if let [lit0, lit1] = clause[..] {
+                graph[-lit0].push(lit1);
+                graph[-lit1].push(lit0);
+            }
+        }
+
+        // 0 means not visited yet
+        let mut index = LitMap::<usize>::new(self.var_count);
+        let mut low = LitMap::<usize>::new(self.var_count);
+        let mut on_stack = LitMap::<bool>::new(self.var_count);
+        let mut stack = vec![];
+        let mut counter = 0;
+        let mut sccs = vec![];
+
+        for var in 1..=self.var_count as Lit {
+            for root in [var, -var] {
+                if index[root] != 0 || graph[root].is_empty() {
+                    continue;
+                }
+
+                counter += 1;
+                index[root] = counter;
+                low[root] = counter;
+                stack.push(root);
+                on_stack[root] = true;
+                // the visited literals and their next successors to visit
+                let mut calls = vec![(root, 0)];
+
+                while let Some(&(lit, i)) = calls.last() {
+                    if let Some(&next) = graph[lit].get(i) {
+                        calls.last_mut().unwrap().1 += 1;
+                        if index[next] == 0 {
+                            counter += 1;
+                            index[next] = counter;
+                            low[next] = counter;
+                            stack.push(next);
+                            on_stack[next] = true;
+                            calls.push((next, 0));
+                        } else if on_stack[next] {
+                            low[lit] = low[lit].min(index[next]);
+                        }
+                        continue;
+                    }
+
+                    calls.pop();
+                    if let Some(&(parent, _)) = calls.last() {
+                        low[parent] = low[parent].min(low[lit]);
+                    }
+                    if low[lit] == index[lit] {
+                        let mut scc = vec![];
+                        loop {
+                            let other = stack.pop().unwrap();
+                            on_stack[other] = false;
+                            scc.push(other);
+                            if other == lit {
+                                break;
+                            }
+                        }
+                        if scc.len() > 1 {
+                            sccs.push(scc);
+                        }
+                    }
+                }
+            }
+        }
+
+        sccs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        preprocess::Preprocessor,
+        types::{Problem, Solution},
+    };
+
+    #[test]
+    fn substitute() {
+        let problem = Problem {
+            var_count: 4,
+            // 1 = 2 = -3
+            clauses: vec![
+                vec![-1, 2],
+                vec![-2, -3],
+                vec![3, 1],
+                vec![1, 3, 4],
+                vec![-2, -4],
+            ],
+            xors: vec![],
+        };
+        let mut preprocessor = Preprocessor::with_proof(problem);
+        preprocessor.substitute();
+
+        let mut clauses = preprocessor.problem().clauses;
+        clauses.sort();
+        assert_eq!(clauses, vec![vec![-4, -1]]);
+
+        let solution = preprocessor.reconstruct(Solution::Sat { model: vec![1, -4] });
+        let Solution::Sat { model } = solution else {
+            panic!("expected a model");
+        };
+        assert_eq!(model, vec![1, 2, -3, -4]);
+
+        let problem = Problem {
+            var_count: 2,
+            clauses: vec![vec![-1, 2], vec![-2, -1], vec![1, -2], vec![2, 1]],
+            xors: vec![],
+        };
+        let mut preprocessor = Preprocessor::new(problem);
+        preprocessor.substitute();
+        assert_eq!(preprocessor.problem().clauses, vec![Vec::<i32>::new()]);
+    }
+}
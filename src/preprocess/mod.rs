@@ -0,0 +1,412 @@
+mod block;
+mod bva;
+mod elim;
+mod equiv;
+
+use std::collections::HashSet;
+
+use crate::{
+    solver::{
+        map::{var_map, LitMap, VarMap},
+        subsume::{self, Action},
+    },
+    types::{to_var, Clause, Lit, Problem, Proof, ProofStep, Solution, Var},
+};
+
+/// Simplifies a [`Problem`] before it is handed to the solver
+/// and maps the models of the simplified problem back to the original one.
+pub struct Preprocessor {
+    var_count: usize,
+    /// The number of variables of the original problem,
+    /// the others are added by [`Preprocessor::add_variables`].
+    original_var_count: usize,
+    /// Sorted clauses, removed ones are `None`.
+    clauses: Vec<Option<Clause>>,
+    /// XOR constraints, passed on unchanged with their variables frozen.
+    xors: Vec<Clause>,
+    /// Indices of the clauses containing each literal.
+    occurs: LitMap<Vec<usize>>,
+    /// Variables fixed by unit clauses.
+    values: VarMap<Option<bool>>,
+    /// Unit literals waiting to be propagated.
+    units: Vec<Lit>,
+    frozen: VarMap<bool>,
+    eliminated: VarMap<bool>,
+    /// Removed clauses needed to reconstruct models, each together with
+    /// the literal to set if the clause is falsified.
+    stack: Vec<(Lit, Clause)>,
+    unsat: bool,
+    proof: Option<Proof>,
+}
+
+impl Preprocessor {
+    pub fn new(problem: Problem) -> Self {
+        Self::create(problem, None)
+    }
+
+    /// Records a DRAT proof.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the problem has XOR constraints, see [`Solver::with_proof`].
+    ///
+    /// [`Solver::with_proof`]: crate::solver::Solver::with_proof
+    pub fn with_proof(problem: Problem) -> Self {
+        assert!(
+            problem.xors.is_empty(),
+            "incompatible input, proofs cannot refer to XOR constraints"
+        );
+        Self::create(problem, Some(vec![]))
+    }
+
+    fn create(problem: Problem, proof: Option<Proof>) -> Self {
+        let var_count = problem.var_count;
+        let mut preprocessor = Self {
+            var_count,
+            original_var_count: var_count,
+            clauses: Vec::with_capacity(problem.clauses.len()),
+            xors: problem.xors,
+            occurs: LitMap::new(var_count),
+            values: var_map(var_count),
+            units: vec![],
+            frozen: var_map(var_count),
+            eliminated: var_map(var_count),
+            stack: vec![],
+            unsat: false,
+            proof,
+        };
+
+        for var in preprocessor.xors.iter().flatten().map(|&lit| to_var(lit)) {
+            preprocessor.frozen[var] = true;
+        }
+        let mut seen = HashSet::new();
+        for mut clause in problem.clauses {
+            clause.sort_unstable();
+            clause.dedup();
+            // duplicates would be matched twice by the grids of BVA
+            if !is_tautology(&clause) && seen.insert(clause.clone()) {
+                preprocessor.add(clause);
+            }
+        }
+        preprocessor.propagate();
+
+        preprocessor
+    }
+
+    /// Prevents `var` from being eliminated, e.g., because it is referred to
+    /// by hints given to the solver.
+    pub fn freeze(&mut self, var: Var) {
+        self.frozen[var] = true;
+    }
+
+    /// Removes subsumed clauses and strengthens clauses
+    /// by self-subsuming resolution.
+    pub fn subsume(&mut self) {
+        if self.unsat {
+            return;
+        }
+
+        let mut indices: Vec<usize> = (0..self.clauses.len())
+            .filter(|&i| self.clauses[i].is_some())
+            .collect();
+        let clauses: Vec<&[Lit]> = indices
+            .iter()
+            .map(|&i| self.clauses[i].as_deref().unwrap())
+            .collect();
+        let actions = subsume::subsume(self.var_count, &clauses, &vec![false; clauses.len()]);
+
+        for action in actions {
+            match action {
+                Action::Remove(i) => {
+                    self.remove(indices[i]);
+                }
+                Action::Strengthen(i, lit) => {
+                    // the strengthened clause is added as a new one
+                    let old = indices[i];
+                    indices[i] = self.clauses.len();
+                    self.strengthen(old, lit);
+                }
+            }
+        }
+        self.propagate();
+    }
+
+    /// The simplified problem over the original variables
+    /// and the added ones.
+    pub fn problem(&self) -> Problem {
+        let clauses = if self.unsat {
+            vec![vec![]]
+        } else {
+            self.clauses.iter().flatten().cloned().collect()
+        };
+        Problem {
+            var_count: self.var_count,
+            clauses,
+            xors: self.xors.clone(),
+        }
+    }
+
+    /// Turns a model of the simplified problem into a model
+    /// of the original one.
+    pub fn reconstruct(&self, solution: Solution) -> Solution {
+        let Solution::Sat { model } = solution else {
+            return solution;
+        };
+
+        let mut values: VarMap<bool> = var_map(self.var_count);
+        for lit in model {
+            values[to_var(lit)] = lit.is_positive();
+        }
+        let is_true = |values: &VarMap<bool>, lit: Lit| values[to_var(lit)] == lit.is_positive();
+
+        for (lit, clause) in self.stack.iter().rev() {
+            if !clause.iter().any(|&other| is_true(&values, other)) {
+                values[to_var(*lit)] = lit.is_positive();
+            }
+        }
+
+        let model = (1..=self.original_var_count)
+            .map(|var| {
+                if values[var] {
+                    var as Lit
+                } else {
+                    -(var as Lit)
+                }
+            })
+            .collect();
+        Solution::Sat { model }
+    }
+
+    pub fn proof(&self) -> Option<&Proof> {
+        self.proof.as_ref()
+    }
+
+    /// Adds a fresh variable, which is left out of the reconstructed models.
+    fn new_var(&mut self) -> Var {
+        self.var_count += 1;
+        self.occurs.grow(self.var_count);
+        self.values.push(None);
+        self.frozen.push(false);
+        self.eliminated.push(false);
+        self.var_count
+    }
+
+    fn add(&mut self, clause: Clause) {
+        match clause[..] {
+            [] => self.set_unsat(),
+            [lit] => self.units.push(lit),
+            _ => (),
+        }
+        let i = self.clauses.len();
+        for &lit in &clause {
+            self.occurs[lit].push(i);
+        }
+        self.clauses.push(Some(clause));
+    }
+
+    /// Adds a clause implied by the current ones.
+    fn derive(&mut self, clause: Clause) {
+        if let Some(proof) = self.proof.as_mut() {
+            proof.push((ProofStep::Add, clause.clone()));
+        }
+        self.add(clause);
+    }
+
+    fn remove(&mut self, i: usize) -> Clause {
+        let clause = self.clauses[i].take().unwrap();
+        for &lit in &clause {
+            self.occurs[lit].retain(|&j| j != i);
+        }
+        if let Some(proof) = self.proof.as_mut() {
+            proof.push((ProofStep::Delete, clause.clone()));
+        }
+        clause
+    }
+
+    /// Removes `lit` from the `i`-th clause, the rest must be implied.
+    fn strengthen(&mut self, i: usize, lit: Lit) {
+        let mut clause = self.clauses[i].clone().unwrap();
+        clause.retain(|&other| other != lit);
+        self.derive(clause);
+        self.remove(i);
+    }
+
+    fn set_unsat(&mut self) {
+        if !self.unsat {
+            self.unsat = true;
+            if let Some(proof) = self.proof.as_mut() {
+                proof.push((ProofStep::Add, vec![]));
+            }
+        }
+    }
+
+    fn value(&self, lit: Lit) -> Option<bool> {
+        self.values[to_var(lit)].map(|value| value == lit.is_positive())
+    }
+
+    /// Removes the clauses satisfied by the unit clauses and the false
+    /// literals from the others. The unit clauses themselves are kept.
+    fn propagate(&mut self) {
+        while let Some(lit) = self.units.pop() {
+            match self.value(lit) {
+                Some(true) => continue,
+                Some(false) => return self.set_unsat(),
+                None => self.values[to_var(lit)] = Some(lit.is_positive()),
+            }
+
+            for i in self.occurs[lit].clone() {
+                if self.clauses[i].as_ref().unwrap().len() > 1 {
+                    self.remove(i);
+                }
+            }
+            for i in self.occurs[-lit].clone() {
+                self.strengthen(i, -lit);
+            }
+        }
+    }
+}
+
+fn is_tautology(sorted: &[Lit]) -> bool {
+    sorted
+        .iter()
+        .any(|&lit| lit > 0 && sorted.binary_search(&-lit).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        solver::random::Rng,
+        types::{Clause, Lit, Problem, Solution},
+    };
+
+    use super::Preprocessor;
+
+    /// Finds a model by trying all assignments.
+    pub(super) fn brute_force(problem: &Problem) -> Option<Vec<Lit>> {
+        (0..1u32 << problem.var_count)
+            .map(|bits| {
+                (1..=problem.var_count as Lit)
+                    .map(|var| {
+                        if bits >> (var - 1) & 1 == 1 {
+                            var
+                        } else {
+                            -var
+                        }
+                    })
+                    .collect::<Vec<Lit>>()
+            })
+            .find(|model| {
+                satisfies(model, &problem.clauses) && satisfies_xors(model, &problem.xors)
+            })
+    }
+
+    pub(super) fn satisfies(model: &[Lit], clauses: &[Clause]) -> bool {
+        clauses
+            .iter()
+            .all(|clause| clause.iter().any(|lit| model.contains(lit)))
+    }
+
+    fn satisfies_xors(model: &[Lit], xors: &[Clause]) -> bool {
+        xors.iter()
+            .all(|xor| xor.iter().filter(|lit| model.contains(lit)).count() % 2 == 1)
+    }
+
+    /// A small problem with clauses of up to three literals.
+    pub(super) fn random_problem(rng: &mut Rng) -> Problem {
+        let var_count = 8;
+        let clauses = (0..rng.below(40))
+            .map(|_| {
+                (0..1 + rng.below(3))
+                    .map(|_| {
+                        let var = 1 + rng.below(var_count) as Lit;
+                        if rng.next_bool() {
+                            var
+                        } else {
+                            -var
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Problem {
+            var_count,
+            clauses,
+            xors: vec![],
+        }
+    }
+
+    #[test]
+    fn units() {
+        let problem = Problem {
+            var_count: 4,
+            clauses: vec![
+                vec![1],
+                vec![-1, 2, 2],
+                vec![1, 3],
+                vec![-2, 3, 4],
+                vec![3, -3],
+            ],
+            xors: vec![],
+        };
+        let preprocessor = Preprocessor::with_proof(problem);
+
+        let mut clauses = preprocessor.problem().clauses;
+        clauses.sort();
+        assert_eq!(clauses, vec![vec![1], vec![2], vec![3, 4]]);
+        assert_eq!(preprocessor.proof().unwrap().len(), 5);
+
+        let problem = Problem {
+            var_count: 2,
+            clauses: vec![vec![1], vec![-1, 2], vec![-2]],
+            xors: vec![],
+        };
+        let preprocessor = Preprocessor::new(problem);
+        assert_eq!(preprocessor.problem().clauses, vec![Vec::<i32>::new()]);
+        let solution = preprocessor.reconstruct(Solution::Unsat);
+        assert!(matches!(solution, Solution::Unsat));
+    }
+
+    #[test]
+    fn xors() {
+        let problem = Problem {
+            var_count: 7,
+            clauses: vec![vec![1, 7], vec![-7, 2], vec![-3, -4]],
+            xors: vec![vec![1, 2, 3, 4, 5, 6], vec![-1, 6]],
+        };
+        let mut preprocessor = Preprocessor::new(problem.clone());
+        preprocessor.eliminate();
+        assert!(preprocessor.eliminated[7]);
+        assert!(!preprocessor.eliminated[1]);
+        let simplified = preprocessor.problem();
+        assert_eq!(simplified.xors, problem.xors);
+
+        let model = brute_force(&simplified).unwrap();
+        let Solution::Sat { model } = preprocessor.reconstruct(Solution::Sat { model }) else {
+            panic!("expected a model");
+        };
+        assert_eq!(model.len(), problem.var_count);
+        assert!(satisfies(&model, &problem.clauses));
+        assert!(satisfies_xors(&model, &problem.xors));
+    }
+
+    #[test]
+    fn subsumption() {
+        let problem = Problem {
+            var_count: 4,
+            clauses: vec![
+                vec![1, 2, 3],
+                vec![1, 2],
+                vec![-1, 2, 4],
+                vec![2, 4],
+                vec![-2, -4],
+            ],
+            xors: vec![],
+        };
+        let mut preprocessor = Preprocessor::with_proof(problem);
+        preprocessor.subsume();
+
+        let mut clauses = preprocessor.problem().clauses;
+        clauses.sort();
+        assert_eq!(clauses, vec![vec![-4, -2], vec![1, 2], vec![2, 4]]);
+    }
+}
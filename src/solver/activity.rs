@@ -1,10 +1,12 @@
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
 
 use crate::types::{Lit, Var};
 
 use super::{
     assignment::Assignment,
+    clause::{ClauseDb, ClauseRef},
     map::{var_map, VarMap},
+    random::Rng,
 };
 
 #[derive(Clone, Copy, PartialEq)]
@@ -39,12 +41,11 @@ struct VarHeap<T> {
 
 impl<T: Ord + Copy> VarHeap<T> {
     fn new(var_count: usize, default: T) -> Self {
-        let mut heap = vec![];
+        let heap: Vec<(T, Var)> = (1..=var_count).map(|var| (default, var)).collect();
         let mut index: VarMap<usize> = var_map(var_count);
 
-        for var in 1..=var_count {
-            index[var] = heap.len();
-            heap.push((default, var));
+        for (pos, &(_, var)) in heap.iter().enumerate() {
+            index[var] = pos;
         }
 
         let size = var_count;
@@ -93,15 +94,17 @@ impl<T: Ord + Copy> VarHeap<T> {
         let pos = self.index[var];
         self.heap[pos] = (val, var);
 
-        self.sift_up(pos);
-        self.sift_down(pos);
+        if pos < self.size {
+            self.sift_up(pos);
+            self.sift_down(pos);
+        }
     }
 
     fn get(&self, var: Var) -> T {
         self.heap[self.index[var]].0
     }
 
-    /// Applies `f` to every value in the heap.
+    /// Applies `f` to every value in the heap (incl. removed items).
     /// The supplied function must preserve the ordering of the items!
     fn transform(&mut self, mut f: impl FnMut(T) -> T) {
         for (val, _) in &mut self.heap {
@@ -109,6 +112,20 @@ impl<T: Ord + Copy> VarHeap<T> {
         }
     }
 
+    fn contains(&self, var: Var) -> bool {
+        self.index[var] < self.size
+    }
+
+    fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the `i`-th item in the heap (in no particular order).
+    fn nth(&self, i: usize) -> Var {
+        debug_assert!(i < self.size);
+        self.heap[i].1
+    }
+
     fn max(&self) -> Option<Var> {
         if self.size != 0 {
             Some(self.heap[0].1)
@@ -117,7 +134,8 @@ impl<T: Ord + Copy> VarHeap<T> {
         }
     }
 
-    fn extract(&mut self) -> Option<Var> {
+    /// Removes the maximum, its value is kept until it is inserted again.
+    fn pop(&mut self) -> Option<Var> {
         if self.size != 0 {
             let var = self.heap[0].1;
             self.swap(0, self.size - 1);
@@ -129,118 +147,269 @@ impl<T: Ord + Copy> VarHeap<T> {
         }
     }
 
-    fn restore(&mut self) -> Option<Var> {
-        if self.size != self.heap.len() {
+    fn insert(&mut self, var: Var) {
+        if !self.contains(var) {
+            self.swap(self.index[var], self.size);
             self.size += 1;
-            let var = self.heap[self.size - 1].1;
             self.sift_up(self.size - 1);
-            Some(var)
-        } else {
-            None
+        }
+    }
+
+    fn remove(&mut self, var: Var) {
+        if self.contains(var) {
+            let pos = self.index[var];
+            self.swap(pos, self.size - 1);
+            self.size -= 1;
+            if pos < self.size {
+                self.sift_up(pos);
+                self.sift_down(pos);
+            }
         }
     }
 }
 
 pub struct Evsids {
     k: f64,
-    seen: VarMap<bool>,
+    decision: VarMap<bool>,
     heap: VarHeap<OrdF64>,
+    /// The variables that are not decision variables, lowest index first.
+    rest: VarHeap<Reverse<Var>>,
 }
 
 impl Evsids {
+    const THRESHOLD: f64 = 10e100;
+
     pub fn new(var_count: usize) -> Self {
+        let mut rest = VarHeap::new(var_count, Reverse(0));
+        for var in 1..=var_count {
+            rest.remove(var);
+            rest.set(var, Reverse(var));
+        }
+
         Self {
             k: 1.0,
-            seen: var_map(var_count),
+            decision: vec![true; var_count + 1],
             heap: VarHeap::new(var_count, OrdF64::new(0.0)),
+            rest,
         }
     }
 
+    /// Bumps `var`, should be called at most once per conflict for each variable.
     pub fn touch(&mut self, var: Var) {
-        if !self.seen[var] {
-            self.seen[var] = true;
+        let val = self.heap.get(var);
+        self.heap.set(var, OrdF64::new(val.0 + self.k));
+    }
 
-            let val = self.heap.get(var);
-            self.heap.set(var, OrdF64::new(val.0 + self.k));
+    /// The current activity of `var`, only meaningful in comparison
+    /// to other activities.
+    pub fn activity(&self, var: Var) -> f64 {
+        self.heap.get(var).0
+    }
+
+    /// Increases the activity of `var` by `amount` regular bumps.
+    pub fn bump(&mut self, var: Var, amount: f64) {
+        let val = self.heap.get(var);
+        self.set_activity(var, val.0 / self.k + amount);
+    }
+
+    /// Sets the activity of `var` to `amount` regular bumps.
+    pub fn set_activity(&mut self, var: Var, amount: f64) {
+        assert!(amount.is_finite() && amount >= 0.0);
+        let val = amount * self.k;
+        self.heap.set(var, OrdF64::new(val));
+        if val > Self::THRESHOLD {
+            self.scale_down();
         }
     }
 
+    fn scale_down(&mut self) {
+        self.heap
+            .transform(|OrdF64(val)| OrdF64::new(val / Self::THRESHOLD));
+        self.k /= Self::THRESHOLD;
+    }
+
     pub fn rescale(&mut self) {
         self.k *= 1.01;
 
-        const THRESHOLD: f64 = 10e100;
-        if self.k > THRESHOLD {
-            self.heap
-                .transform(|OrdF64(val)| OrdF64::new(val / THRESHOLD));
-            self.k /= THRESHOLD;
-        }
-
-        for var_seen in &mut self.seen {
-            *var_seen = false;
+        if self.k > Self::THRESHOLD {
+            self.scale_down();
         }
     }
 
+    /// Returns the most active unassigned decision variable.
+    /// Assigned variables are removed from the heap on the way,
+    /// they need to be [`inserted`](Evsids::insert) back once unassigned.
+    /// Once all decision variables are assigned, the remaining variables
+    /// are returned in order of their index.
     pub fn choose(&mut self, assignment: &Assignment) -> Option<Var> {
-        let mut res = None;
-
         while let Some(var) = self.heap.max() {
             if assignment.eval(var as Lit).is_none() {
-                res = Some(var);
-                break;
+                return Some(var);
             }
-            self.heap.extract();
+            self.heap.pop();
         }
-        while self.heap.restore().is_some() {}
-
-        res
+        while let Some(var) = self.rest.max() {
+            if assignment.eval(var as Lit).is_none() {
+                return Some(var);
+            }
+            self.rest.pop();
+        }
+        None
     }
-}
 
-pub struct ClauseTracker {
-    k: f64,
-    activity: Vec<OrdF64>,
-}
+    /// Returns a random variable from the heap if it is unassigned.
+    pub fn choose_random(&self, assignment: &Assignment, rng: &mut Rng) -> Option<Var> {
+        if self.heap.len() == 0 {
+            return None;
+        }
+        let var = self.heap.nth(rng.below(self.heap.len()));
+        assignment.eval(var as Lit).is_none().then_some(var)
+    }
 
-impl ClauseTracker {
-    pub fn new(clause_count: usize) -> Self {
-        Self {
-            k: 1.0,
-            activity: Vec::with_capacity(clause_count),
+    pub fn insert(&mut self, var: Var) {
+        if self.decision[var] {
+            self.heap.insert(var);
+        } else {
+            self.rest.insert(var);
         }
     }
 
-    pub fn add(&mut self) {
-        self.activity.push(OrdF64::new(0.0));
+    /// Sets whether `var` is eligible for branching by [`Evsids::choose`].
+    pub fn set_decision(&mut self, var: Var, decision: bool) {
+        self.decision[var] = decision;
+        if decision {
+            self.rest.remove(var);
+            self.heap.insert(var);
+        } else {
+            self.heap.remove(var);
+            self.rest.insert(var);
+        }
     }
 
-    pub fn swap_remove(&mut self, i_clause: usize) -> f64 {
-        self.activity.swap_remove(i_clause).0
+    /// Adds a small random amount to each activity,
+    /// which shuffles the initial order of the variables.
+    pub fn perturb(&mut self, rng: &mut Rng) {
+        for var in 1..self.decision.len() {
+            let val = self.heap.get(var);
+            self.heap
+                .set(var, OrdF64::new(val.0 + rng.next_f64() * 1e-5));
+        }
     }
+}
+
+/// Tracks the activity of learnt clauses, the values are stored
+/// in the clause headers.
+pub struct ClauseTracker {
+    k: f64,
+}
 
-    pub fn touch(&mut self, i_clause: usize) {
-        self.activity[i_clause] = OrdF64::new(self.activity[i_clause].0 + self.k);
+impl ClauseTracker {
+    pub fn new() -> Self {
+        Self { k: 1.0 }
     }
 
-    pub fn get_activity(&mut self, i_clause: usize) -> f64 {
-        self.activity[i_clause].0
+    pub fn touch(&mut self, db: &mut ClauseDb, c: ClauseRef) {
+        if db.is_learnt(c) {
+            let activity = db.activity(c) as f64 + self.k;
+            db.set_activity(c, activity as f32);
+        }
     }
 
-    pub fn select_pivot(&self, start: usize) -> f64 {
-        let mut v: Vec<OrdF64> = self.activity[start..].to_vec();
-        v.sort();
-        let pivot = v[v.len() / 2];
-        pivot.0
+    pub fn select_pivot(&self, db: &ClauseDb, learnts: &[ClauseRef]) -> f32 {
+        let mut v: Vec<f32> = learnts.iter().map(|&c| db.activity(c)).collect();
+        v.sort_by(f32::total_cmp);
+        v[v.len() / 2]
     }
 
-    pub fn rescale(&mut self) {
+    pub fn rescale(&mut self, db: &mut ClauseDb, learnts: &[ClauseRef]) {
         self.k *= 1.001;
 
         const THRESHOLD: f64 = 10e20;
         if self.k > THRESHOLD {
-            for val in &mut self.activity {
-                *val = OrdF64::new(val.0 / THRESHOLD);
+            for &c in learnts {
+                let activity = db.activity(c) as f64 / THRESHOLD;
+                db.set_activity(c, activity as f32);
             }
             self.k /= THRESHOLD;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::assignment::Assignment;
+
+    use super::{Evsids, VarHeap};
+
+    #[test]
+    fn heap() {
+        let mut heap = VarHeap::new(4, 0);
+        for var in 1..=4 {
+            heap.set(var, var * 10);
+        }
+
+        assert_eq!(heap.pop(), Some(4));
+        assert_eq!(heap.pop(), Some(3));
+        assert!(!heap.contains(4));
+
+        // values of removed items are kept and can still be updated
+        heap.set(3, 5);
+        assert_eq!(heap.max(), Some(2));
+
+        heap.insert(4);
+        heap.insert(3);
+        heap.insert(3);
+        heap.remove(1);
+        heap.insert(1);
+        assert_eq!(heap.get(4), 40);
+        assert_eq!(heap.pop(), Some(4));
+        assert_eq!(heap.pop(), Some(2));
+        assert_eq!(heap.pop(), Some(1));
+        assert_eq!(heap.pop(), Some(3));
+        assert_eq!(heap.pop(), None);
+    }
+
+    #[test]
+    fn decision() {
+        let mut assignment = Assignment::new(4);
+        let mut evsids = Evsids::new(4);
+        evsids.set_activity(1, 1.0);
+        evsids.set_decision(1, false);
+        evsids.set_decision(3, false);
+        evsids.set_decision(4, false);
+
+        assert_eq!(evsids.choose(&assignment), Some(2));
+        assignment.decide(2);
+        // the other variables by index once the decision variables
+        // are assigned
+        assert_eq!(evsids.choose(&assignment), Some(1));
+        assignment.decide(1);
+        assert_eq!(evsids.choose(&assignment), Some(3));
+
+        assignment.backtrack(0, |var| evsids.insert(var));
+        evsids.set_decision(3, true);
+        assignment.decide(2);
+        assert_eq!(evsids.choose(&assignment), Some(3));
+        assignment.decide(3);
+        assert_eq!(evsids.choose(&assignment), Some(1));
+    }
+
+    #[test]
+    fn bump() {
+        let assignment = Assignment::new(3);
+        let mut evsids = Evsids::new(3);
+
+        evsids.set_activity(3, 2.0);
+        assert_eq!(evsids.choose(&assignment), Some(3));
+
+        evsids.bump(1, 2.5);
+        assert_eq!(evsids.choose(&assignment), Some(1));
+
+        // huge activities are scaled down together with the increment
+        evsids.set_activity(2, 1e120);
+        assert_eq!(evsids.choose(&assignment), Some(2));
+        evsids.touch(1);
+        evsids.rescale();
+        assert_eq!(evsids.choose(&assignment), Some(2));
+    }
+}
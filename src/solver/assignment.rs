@@ -1,11 +1,16 @@
 use crate::types::{to_var, Lit, Var};
 
-use super::map::{var_map, VarMap};
+use super::{
+    clause::ClauseRef,
+    map::{var_map, VarMap},
+    random::Rng,
+    rephase::Rephase,
+};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Reason {
     Decision,
-    Propagation { i_clause: usize },
+    Propagation { clause: ClauseRef },
 }
 
 #[derive(Clone)]
@@ -13,11 +18,23 @@ struct VarData {
     value: bool,
     level: usize,
     reason: Reason,
+    /// Index in the trail.
+    position: usize,
 }
 
 pub struct Assignment {
     data: VarMap<Option<VarData>>,
+    original: VarMap<bool>,
+    /// Whether the original phase was chosen by the user.
+    pinned: VarMap<bool>,
     saved: VarMap<bool>,
+    /// The phases on the largest conflict-free trail, the other
+    /// variables are decided by their saved phases.
+    target: VarMap<Option<bool>>,
+    target_vars: Vec<Var>,
+    target_len: usize,
+    best: VarMap<bool>,
+    best_len: usize,
     trail: Vec<Lit>,
     levels: Vec<usize>,
 }
@@ -26,7 +43,14 @@ impl Assignment {
     pub fn new(var_count: usize) -> Self {
         Self {
             data: var_map(var_count),
+            original: var_map(var_count),
+            pinned: var_map(var_count),
             saved: var_map(var_count),
+            target: var_map(var_count),
+            target_vars: vec![],
+            target_len: 0,
+            best: var_map(var_count),
+            best_len: 0,
             trail: vec![],
             levels: vec![],
         }
@@ -38,29 +62,139 @@ impl Assignment {
             .map(|data| data.value == lit.is_positive())
     }
 
+    /// Assigns `lit` at the last decision level,
+    /// or at a new one if it is a decision.
     pub fn set(&mut self, lit: Lit, reason: Reason) {
-        self.trail.push(lit);
-
         if let Reason::Decision = reason {
-            self.levels.push(self.trail.len() - 1);
+            self.levels.push(self.trail.len());
         }
+        self.assign(lit, reason, self.last_level());
+    }
+
+    /// Assigns `lit` implied by `clause` at `level`, i.e., the highest level
+    /// among the other literals of the clause, which may be lower than
+    /// the last decision level.
+    pub fn imply(&mut self, lit: Lit, clause: ClauseRef, level: usize) {
+        debug_assert!(level <= self.last_level());
+        self.assign(lit, Reason::Propagation { clause }, level);
+    }
 
+    fn assign(&mut self, lit: Lit, reason: Reason, level: usize) {
         let data = VarData {
             value: lit.is_positive(),
-            level: self.last_level(),
+            level,
             reason,
+            position: self.trail.len(),
         };
         let var = to_var(lit);
         self.saved[var] = data.value;
         self.data[var] = Some(data);
+        self.trail.push(lit);
     }
 
     pub fn decide(&mut self, var: Var) {
         let lvar = var as Lit;
-        let lit = if self.saved[var] { lvar } else { -lvar };
+        let phase = self.target[var].unwrap_or(self.saved[var]);
+        let lit = if phase { lvar } else { -lvar };
         self.set(lit, Reason::Decision);
     }
 
+    /// Updates the target and best phases if the first `len` literals
+    /// of the trail, which must be conflict-free, form a new maximum.
+    pub fn update_phases(&mut self, len: usize) {
+        if len > self.target_len {
+            self.target_len = len;
+            self.clear_target();
+            for &lit in &self.trail[..len] {
+                self.target[to_var(lit)] = Some(lit.is_positive());
+                self.target_vars.push(to_var(lit));
+            }
+        }
+        if len > self.best_len {
+            self.best_len = len;
+            for &lit in &self.trail[..len] {
+                self.best[to_var(lit)] = lit.is_positive();
+            }
+        }
+    }
+
+    /// The length of the trail without the last decision level.
+    pub fn consistent_len(&self) -> usize {
+        self.levels.last().copied().unwrap_or(self.trail.len())
+    }
+
+    /// Forgets the largest trail seen since the last restart,
+    /// the target phases are kept until a larger trail is found.
+    pub fn reset_target(&mut self) {
+        self.target_len = 0;
+    }
+
+    pub fn saved_phases(&self) -> &VarMap<bool> {
+        &self.saved
+    }
+
+    /// Overwrites the saved phases according to `kind`
+    /// and drops the target phases.
+    /// [`Rephase::Walk`] is not handled here, see [`Assignment::set_phases`].
+    pub fn rephase(&mut self, kind: Rephase, rng: &mut Rng) {
+        match kind {
+            Rephase::Original => self.saved.clone_from(&self.original),
+            Rephase::Inverted => {
+                for (saved, original) in self.saved.iter_mut().zip(&self.original) {
+                    *saved = !original;
+                }
+            }
+            Rephase::Best => self.saved.clone_from(&self.best),
+            Rephase::Random => self.saved.iter_mut().for_each(|val| *val = rng.next_bool()),
+            Rephase::Walk => (),
+        }
+        self.reset_maxima();
+    }
+
+    /// Randomizes the saved phases of the variables
+    /// whose initial phase was not set by [`Assignment::set_original_phase`].
+    pub fn perturb_phases(&mut self, rng: &mut Rng) {
+        for var in 1..=self.var_count() {
+            if !self.pinned[var] {
+                self.saved[var] = rng.next_bool();
+            }
+        }
+    }
+
+    /// Sets the initial phase of `var`, which is also the one
+    /// restored by [`Rephase::Original`].
+    pub fn set_original_phase(&mut self, var: Var, phase: bool) {
+        self.original[var] = phase;
+        self.pinned[var] = true;
+        self.saved[var] = phase;
+    }
+
+    pub fn set_phases(&mut self, phases: VarMap<bool>) {
+        self.saved = phases;
+        self.reset_maxima();
+    }
+
+    /// Overwrites the saved phases only, e.g., to undo tentative assignments.
+    pub fn restore_saved_phases(&mut self, phases: VarMap<bool>) {
+        self.saved = phases;
+    }
+
+    fn reset_maxima(&mut self) {
+        self.clear_target();
+        self.target_len = 0;
+        self.best_len = 0;
+    }
+
+    fn clear_target(&mut self) {
+        for var in self.target_vars.drain(..) {
+            self.target[var] = None;
+        }
+    }
+
+    pub fn var_count(&self) -> usize {
+        self.data.len() - 1
+    }
+
     pub fn trail(&self) -> &[Lit] {
         &self.trail
     }
@@ -73,26 +207,65 @@ impl Assignment {
         self.data[to_var(lit)].as_ref().map(|data| data.reason)
     }
 
+    /// The index of `lit` in the trail.
+    pub fn position(&self, lit: Lit) -> Option<usize> {
+        self.data[to_var(lit)].as_ref().map(|data| data.position)
+    }
+
+    /// The decision made at `level`, which must be at least 1.
+    pub fn decision(&self, level: usize) -> Lit {
+        self.trail[self.levels[level - 1]]
+    }
+
     pub fn last_level(&self) -> usize {
         self.levels.len()
     }
 
-    /// Revert all changes at `level` (incl.) and above.
-    pub fn backtrack(&mut self, level: usize) {
-        self.levels.drain(level..);
-        let i = self.levels.pop().unwrap_or(0);
-        for lit in self.trail.drain(i..) {
-            self.data[to_var(lit)] = None;
+    /// Revert all changes at `level` (incl.) and above,
+    /// `unassigned` is called for every variable that becomes unassigned.
+    /// Literals at lower levels assigned after the decision at `level`
+    /// are kept in the same order.
+    /// Returns the index of the first trail literal that was removed
+    /// or moved, everything from there on needs to be propagated again.
+    pub fn backtrack(&mut self, level: usize, mut unassigned: impl FnMut(Var)) -> usize {
+        self.levels.truncate(level);
+        let start = self.levels.pop().unwrap_or(0);
+
+        let mut j = start;
+        for i in start..self.trail.len() {
+            let lit = self.trail[i];
+            let var = to_var(lit);
+            let data = self.data[var].as_mut().unwrap();
+            if data.level < level {
+                data.position = j;
+                self.trail[j] = lit;
+                j += 1;
+            } else {
+                self.data[var] = None;
+                unassigned(var);
+            }
+        }
+        self.trail.truncate(j);
+
+        start
+    }
+
+    /// Drops the reasons of the literals at level 0, which are never needed
+    /// in conflict analysis, so that the clauses can be removed.
+    /// The literals become indistinguishable from decisions.
+    pub fn forget_reasons(&mut self) {
+        debug_assert_eq!(self.last_level(), 0);
+        for &lit in &self.trail {
+            self.data[to_var(lit)].as_mut().unwrap().reason = Reason::Decision;
         }
     }
 
-    pub fn rename_clause(&mut self, i: usize, j: usize) {
+    /// Updates the reasons after the clauses have been moved.
+    pub fn relocate(&mut self, mut f: impl FnMut(ClauseRef) -> ClauseRef) {
         for &lit in &self.trail {
             let data = self.data[to_var(lit)].as_mut().unwrap();
-            if let Reason::Propagation { i_clause } = data.reason {
-                if i_clause == i {
-                    data.reason = Reason::Propagation { i_clause: j };
-                }
+            if let Reason::Propagation { clause } = data.reason {
+                data.reason = Reason::Propagation { clause: f(clause) };
             }
         }
     }
@@ -100,23 +273,99 @@ impl Assignment {
 
 #[cfg(test)]
 mod tests {
+    use crate::solver::{clause::ClauseDb, random::Rng, rephase::Rephase};
+
     use super::{Assignment, Reason};
 
     #[test]
     fn basic() {
         let mut ass = Assignment::new(2);
+        let clause = ClauseDb::with_capacity(0).add(&[1, -2], false);
 
         assert_eq!(ass.last_level(), 0);
 
         ass.set(1, Reason::Decision);
-        ass.set(-2, Reason::Propagation { i_clause: 0 });
+        ass.set(-2, Reason::Propagation { clause });
 
         assert_eq!(ass.last_level(), 1);
+        assert_eq!(ass.decision(1), 1);
         assert_eq!(ass.level(1), Some(1));
         assert_eq!(ass.level(2), Some(1));
 
-        ass.backtrack(1);
+        let mut unassigned = vec![];
+        ass.backtrack(1, |var| unassigned.push(var));
+        assert_eq!(unassigned, vec![1, 2]);
         assert_eq!(ass.eval(2), None);
         assert_eq!(ass.eval(1), None);
     }
+
+    #[test]
+    fn out_of_order() {
+        let mut ass = Assignment::new(4);
+        let clause = ClauseDb::with_capacity(0).add(&[1, 3], false);
+
+        ass.set(1, Reason::Decision);
+        ass.set(2, Reason::Decision);
+        // implied by a clause with literals only at level 1
+        ass.imply(3, clause, 1);
+        ass.set(4, Reason::Decision);
+
+        assert_eq!(ass.last_level(), 3);
+        assert_eq!(ass.level(3), Some(1));
+        assert_eq!(ass.position(3), Some(2));
+
+        let mut unassigned = vec![];
+        let start = ass.backtrack(2, |var| unassigned.push(var));
+        assert_eq!(start, 1);
+        assert_eq!(unassigned, vec![2, 4]);
+        assert_eq!(ass.trail(), &[1, 3]);
+        assert_eq!(ass.position(3), Some(1));
+        assert_eq!(ass.last_level(), 1);
+    }
+
+    #[test]
+    fn phases() {
+        let mut ass = Assignment::new(3);
+        let mut rng = Rng::new(0);
+        let decide_all = |ass: &mut Assignment| {
+            for var in 1..=3 {
+                ass.decide(var);
+            }
+            let trail = ass.trail().to_vec();
+            ass.backtrack(1, |_| ());
+            trail
+        };
+
+        assert_eq!(decide_all(&mut ass), vec![-1, -2, -3]);
+
+        ass.set(1, Reason::Decision);
+        ass.set(2, Reason::Decision);
+        ass.set(3, Reason::Decision);
+        // the last decision level is not known to be conflict-free
+        ass.update_phases(ass.consistent_len());
+        ass.backtrack(1, |_| ());
+        // 3 is not on the target trail, its saved phase is used
+        assert_eq!(decide_all(&mut ass), vec![1, 2, 3]);
+
+        // a shorter trail is no new maximum
+        ass.set(-1, Reason::Decision);
+        ass.set(-2, Reason::Decision);
+        ass.set(-3, Reason::Decision);
+        ass.update_phases(ass.consistent_len());
+        ass.backtrack(1, |_| ());
+        assert_eq!(decide_all(&mut ass), vec![1, 2, -3]);
+
+        // rephasing drops the target phases and resets the maxima
+        ass.rephase(Rephase::Inverted, &mut rng);
+        assert_eq!(decide_all(&mut ass), vec![1, 2, 3]);
+        ass.set(-1, Reason::Decision);
+        ass.set(-2, Reason::Decision);
+        ass.update_phases(ass.consistent_len());
+        ass.backtrack(1, |_| ());
+        assert_eq!(decide_all(&mut ass), vec![-1, -2, 3]);
+
+        // the best phases were updated along with the target ones
+        ass.rephase(Rephase::Best, &mut rng);
+        assert_eq!(decide_all(&mut ass), vec![-1, 2, -3]);
+    }
 }
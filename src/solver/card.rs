@@ -0,0 +1,491 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+};
+
+use crate::types::{to_var, Clause, Lit};
+
+use super::{
+    assignment::Assignment,
+    clause::{ClauseDb, ClauseRef},
+    map::LitMap,
+    Solver,
+};
+
+/// Smaller sets of mutually exclusive literals are left to the clauses.
+const MIN_AMO_LEN: usize = 4;
+/// The binomial encoding of "at most k" has clauses of length k + 1.
+const MAX_AMK_CLAUSE_LEN: usize = 4;
+const MAX_AMK_LEN: usize = 32;
+/// Literals collected from the binary implication graph per literal.
+const MAX_REACH: usize = 1000;
+const MAX_EFFORT: usize = 1 << 26;
+
+/// At most `bound` of `lits` are true.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Card {
+    pub lits: Vec<Lit>,
+    pub bound: usize,
+}
+
+/// Also returns the indices of the clauses the constraints replace.
+///
+/// At-most-one constraints are cliques in the binary implication graph,
+/// covering pairwise encodings and sequential counters, whose clauses are
+/// kept. Larger bounds are only found in the binomial encoding, for k up to
+/// `MAX_AMK_CLAUSE_LEN - 1`.
+pub fn detect(var_count: usize, clauses: &[&[Lit]]) -> (Vec<Card>, Vec<usize>) {
+    let mut cards = vec![];
+    let mut covered = vec![];
+    let mut effort = 0;
+    detect_amo(var_count, clauses, &mut cards, &mut covered, &mut effort);
+    for len in 3..=MAX_AMK_CLAUSE_LEN {
+        detect_amk(clauses, len, &mut cards, &mut covered, &mut effort);
+    }
+    covered.sort_unstable();
+    covered.dedup();
+    (cards, covered)
+}
+
+fn detect_amo(
+    var_count: usize,
+    clauses: &[&[Lit]],
+    cards: &mut Vec<Card>,
+    covered: &mut Vec<usize>,
+    effort: &mut usize,
+) {
+    // the literals implied through binary clauses and the binary clauses
+    // by the pairs of literals they exclude
+    let mut implied = LitMap::<Vec<Lit>>::new(var_count);
+    let mut binary = HashMap::new();
+    for (i, clause) in clauses.iter().enumerate() {
+        if let &[a, b] = *clause {
+            implied[-a].push(b);
+            implied[-b].push(a);
+            binary.insert(pair(-a, -b), i);
+        }
+    }
+    if binary.is_empty() {
+        return;
+    }
+
+    let lits: Vec<Lit> = (1..=var_count as Lit).flat_map(|var| [var, -var]).collect();
+    // Literals not implied by any binary clause, e.g., the inputs
+    // of a sequential counter, also exclude the literals whose negations
+    // they imply transitively. Other literals, e.g., the auxiliary ones,
+    // are left to the direct exclusions to avoid overlapping constraints.
+    let is_source = |lit: Lit| implied[-lit].is_empty();
+    // the literals which cannot be true together with each literal, sorted
+    let mut excluded = LitMap::<Vec<Lit>>::new(var_count);
+    let mut seen = LitMap::<bool>::new(var_count);
+    for &lit in &lits {
+        if implied[lit].is_empty() || *effort > MAX_EFFORT {
+            continue;
+        }
+        // breadth-first search, the direct implications are always included
+        let mut reached = vec![lit];
+        seen[lit] = true;
+        let mut direct = 1;
+        let mut i = 0;
+        while i < reached.len() && (i == 0 || (is_source(lit) && reached.len() < MAX_REACH)) {
+            for &next in &implied[reached[i]] {
+                if !seen[next] {
+                    seen[next] = true;
+                    reached.push(next);
+                }
+            }
+            if i == 0 {
+                direct = reached.len();
+            }
+            i += 1;
+        }
+        for &other in &reached {
+            seen[other] = false;
+        }
+        *effort += reached.len();
+
+        let mut list: Vec<Lit> = reached
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|&(j, &other)| j < direct || is_source(-other))
+            .map(|(_, &other)| -other)
+            .filter(|&other| to_var(other) != to_var(lit))
+            .collect();
+        list.sort_unstable();
+        excluded[lit] = list;
+    }
+
+    let excludes = |a: Lit, b: Lit| {
+        excluded[a].binary_search(&b).is_ok() || excluded[b].binary_search(&a).is_ok()
+    };
+    let mut order: Vec<Lit> = lits
+        .into_iter()
+        .filter(|&lit| !excluded[lit].is_empty())
+        .collect();
+    order.sort_by_key(|&lit| Reverse(excluded[lit].len()));
+
+    // Cliques of transitive exclusions overlap heavily, e.g., after
+    // bounded variable addition, so their total size is limited
+    // by the number of binary clauses.
+    let mut size = 0;
+    // pairs already covered by a constraint or known not to extend to one
+    let mut done = HashSet::new();
+    for &a in &order {
+        for &b in &excluded[a] {
+            if *effort > MAX_EFFORT || size > binary.len() {
+                return;
+            }
+            if done.contains(&pair(a, b)) {
+                continue;
+            }
+            // grows the clique greedily, preferring literals excluding many others
+            let mut candidates: Vec<Lit> = excluded[a]
+                .iter()
+                .copied()
+                .filter(|&c| c != b && excludes(b, c))
+                .collect();
+            candidates.sort_by_key(|&c| Reverse(excluded[c].len()));
+            let mut clique = vec![a, b];
+            for c in candidates {
+                *effort += clique.len();
+                if clique[2..].iter().all(|&d| excludes(c, d)) {
+                    clique.push(c);
+                }
+            }
+
+            if clique.len() < MIN_AMO_LEN {
+                done.insert(pair(a, b));
+                continue;
+            }
+            for (i, &c) in clique.iter().enumerate() {
+                for &d in &clique[..i] {
+                    done.insert(pair(c, d));
+                    if let Some(&j) = binary.get(&pair(c, d)) {
+                        covered.push(j);
+                    }
+                }
+            }
+            size += clique.len();
+            clique.sort_unstable();
+            cards.push(Card {
+                lits: clique,
+                bound: 1,
+            });
+        }
+    }
+}
+
+fn detect_amk(
+    clauses: &[&[Lit]],
+    len: usize,
+    cards: &mut Vec<Card>,
+    covered: &mut Vec<usize>,
+    effort: &mut usize,
+) {
+    // the negated literals of the clauses, sorted, with their indices
+    let mut sets: Vec<(Vec<Lit>, usize)> = clauses
+        .iter()
+        .enumerate()
+        .filter(|(_, clause)| clause.len() == len)
+        .map(|(i, clause)| {
+            let mut lits: Vec<Lit> = clause.iter().map(|&lit| -lit).collect();
+            lits.sort_unstable();
+            (lits, i)
+        })
+        .collect();
+    sets.sort_unstable();
+    sets.dedup_by(|a, b| a.0 == b.0);
+    let index: HashMap<&[Lit], usize> = sets
+        .iter()
+        .enumerate()
+        .map(|(k, (lits, _))| (lits.as_slice(), k))
+        .collect();
+    let mut occurs: HashMap<Lit, Vec<usize>> = HashMap::new();
+    for (k, (lits, _)) in sets.iter().enumerate() {
+        for &lit in lits {
+            occurs.entry(lit).or_default().push(k);
+        }
+    }
+
+    let mut done = vec![false; sets.len()];
+    for k in 0..sets.len() {
+        if done[k] || *effort > MAX_EFFORT {
+            continue;
+        }
+        // all the literals of the constraint occur together with the first one
+        let mut lits = sets[k].0.clone();
+        let mut candidates: Vec<Lit> = occurs[&lits[0]]
+            .iter()
+            .flat_map(|&j| sets[j].0.iter().copied())
+            .filter(|&lit| !lits.iter().any(|&other| to_var(other) == to_var(lit)))
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+        for lit in candidates {
+            if lits.len() == MAX_AMK_LEN {
+                break;
+            }
+            let extends = subsets(&lits, len - 1).all(|mut subset| {
+                *effort += 1;
+                subset.push(lit);
+                subset.sort_unstable();
+                index.contains_key(subset.as_slice())
+            });
+            if extends {
+                lits.push(lit);
+            }
+        }
+
+        if lits.len() == len {
+            // just the clause itself
+            continue;
+        }
+        lits.sort_unstable();
+        for subset in subsets(&lits, len) {
+            let j = index[subset.as_slice()];
+            done[j] = true;
+            covered.push(sets[j].1);
+        }
+        cards.push(Card {
+            lits,
+            bound: len - 1,
+        });
+    }
+}
+
+fn pair(a: Lit, b: Lit) -> (Lit, Lit) {
+    (a.min(b), a.max(b))
+}
+
+fn subsets(lits: &[Lit], size: usize) -> impl Iterator<Item = Vec<Lit>> + '_ {
+    let mut positions: Vec<usize> = (0..size).collect();
+    let mut finished = size > lits.len();
+    std::iter::from_fn(move || {
+        if finished {
+            return None;
+        }
+        let subset = positions.iter().map(|&i| lits[i]).collect();
+        match (0..size)
+            .rev()
+            .find(|&i| positions[i] != i + lits.len() - size)
+        {
+            Some(i) => {
+                positions[i] += 1;
+                for j in i + 1..size {
+                    positions[j] = positions[j - 1] + 1;
+                }
+            }
+            None => finished = true,
+        }
+        Some(subset)
+    })
+}
+
+pub struct Cards {
+    cards: Vec<Card>,
+    occurs: LitMap<Vec<usize>>,
+    /// The clauses replaced by the constraints, kept for the local search.
+    pub covered: Vec<Clause>,
+}
+
+impl Cards {
+    pub fn new(var_count: usize, cards: Vec<Card>) -> Self {
+        let mut occurs = LitMap::<Vec<usize>>::new(var_count);
+        for (i, card) in cards.iter().enumerate() {
+            for &lit in &card.lits {
+                occurs[lit].push(i);
+            }
+        }
+        Self {
+            cards,
+            occurs,
+            covered: vec![],
+        }
+    }
+
+    pub fn propagate(
+        &mut self,
+        lit: Lit,
+        assignment: &mut Assignment,
+        db: &mut ClauseDb,
+    ) -> Option<ClauseRef> {
+        for k in 0..self.occurs[lit].len() {
+            let card = &self.cards[self.occurs[lit][k]];
+            // the negations of the true literals, `lit` first
+            let mut falsified = vec![-lit];
+            falsified.extend(
+                card.lits
+                    .iter()
+                    .filter(|&&other| other != lit && assignment.eval(other) == Some(true))
+                    .map(|&other| -other),
+            );
+            if falsified.len() > card.bound {
+                falsified.truncate(card.bound + 1);
+                return Some(db.add_temporary(&falsified));
+            }
+            if falsified.len() < card.bound {
+                continue;
+            }
+
+            let unassigned: Vec<Lit> = card
+                .lits
+                .iter()
+                .copied()
+                .filter(|&other| assignment.eval(other).is_none())
+                .collect();
+            let level = falsified
+                .iter()
+                .map(|&other| assignment.level(other).unwrap())
+                .max()
+                .unwrap();
+            for other in unassigned {
+                // the implied literal is stored first
+                let lits: Vec<Lit> = std::iter::once(-other)
+                    .chain(falsified.iter().copied())
+                    .collect();
+                let c = db.add_temporary(&lits);
+                assignment.imply(-other, c, level);
+            }
+        }
+        None
+    }
+}
+
+impl Solver {
+    /// The clauses replaced by the constraints are removed.
+    pub(super) fn enable_cards(&mut self) -> bool {
+        let clauses: Vec<&[Lit]> = self.clauses.iter().map(|&c| self.db.lits(c)).collect();
+        let (cards, covered) = detect(self.assignment.var_count(), &clauses);
+        if cards.is_empty() {
+            return true;
+        }
+
+        let mut removed = vec![];
+        for i in covered {
+            let lits = self.db.lits(self.clauses[i]).to_vec();
+            // the reasons of facts are kept
+            if self.remove(self.clauses[i]) {
+                removed.push(lits);
+            }
+        }
+        let db = &self.db;
+        self.clauses.retain(|&c| !db.is_deleted(c));
+        for watched in self.watched.iter_mut().chain(self.binary.iter_mut()) {
+            watched.retain(|watch| !db.is_deleted(watch.clause));
+        }
+
+        let mut cards = Cards::new(self.assignment.var_count(), cards);
+        cards.covered = removed;
+        self.cards = Some(cards);
+        // the literals assigned so far are new to the constraints
+        self.card_head = 0;
+        self.propagate().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        assignment::{Assignment, Reason},
+        clause::ClauseDb,
+    };
+
+    use super::{detect, subsets, Card, Cards};
+
+    #[test]
+    fn pairwise() {
+        let mut clauses: Vec<Vec<i32>> = vec![vec![1, 2, 3, 4, 5]];
+        for a in 1..=5 {
+            for b in a + 1..=5 {
+                clauses.push(vec![-a, -b]);
+            }
+        }
+        // too small
+        clauses.extend([vec![6, 7], vec![6, 8], vec![7, 8]]);
+        let lits: Vec<&[i32]> = clauses.iter().map(Vec::as_slice).collect();
+
+        let (cards, covered) = detect(8, &lits);
+        assert_eq!(
+            cards,
+            vec![Card {
+                lits: vec![1, 2, 3, 4, 5],
+                bound: 1
+            }]
+        );
+        assert_eq!(covered, (1..=10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn sequential_counter() {
+        // at most one of 1..=5 with the auxiliary variables 6..=9
+        let clauses: Vec<Vec<i32>> = vec![
+            vec![-1, 6],
+            vec![-2, 7],
+            vec![-6, 7],
+            vec![-2, -6],
+            vec![-3, 8],
+            vec![-7, 8],
+            vec![-3, -7],
+            vec![-4, 9],
+            vec![-8, 9],
+            vec![-4, -8],
+            vec![-5, -9],
+        ];
+        let lits: Vec<&[i32]> = clauses.iter().map(Vec::as_slice).collect();
+
+        let (cards, covered) = detect(9, &lits);
+        assert_eq!(
+            cards,
+            vec![Card {
+                lits: vec![1, 2, 3, 4, 5],
+                bound: 1
+            }]
+        );
+        // the auxiliary clauses are kept
+        assert!(covered.is_empty());
+    }
+
+    #[test]
+    fn binomial() {
+        // at most two of 1..=5
+        let clauses: Vec<Vec<i32>> = subsets(&[-1, -2, -3, -4, -5], 3).collect();
+        let lits: Vec<&[i32]> = clauses.iter().map(Vec::as_slice).collect();
+
+        let (cards, covered) = detect(5, &lits);
+        assert_eq!(
+            cards,
+            vec![Card {
+                lits: vec![1, 2, 3, 4, 5],
+                bound: 2
+            }]
+        );
+        assert_eq!(covered.len(), clauses.len());
+    }
+
+    #[test]
+    fn propagation() {
+        let card = Card {
+            lits: vec![1, 2, 3, 4],
+            bound: 2,
+        };
+        let mut cards = Cards::new(4, vec![card]);
+        let mut assignment = Assignment::new(4);
+        let mut db = ClauseDb::with_capacity(0);
+
+        assignment.set(1, Reason::Decision);
+        assert_eq!(cards.propagate(1, &mut assignment, &mut db), None);
+        assert_eq!(assignment.eval(2), None);
+
+        assignment.set(2, Reason::Decision);
+        assert_eq!(cards.propagate(2, &mut assignment, &mut db), None);
+        for lit in [-3, -4] {
+            assert_eq!(assignment.eval(lit), Some(true));
+            let Some(Reason::Propagation { clause }) = assignment.reason(lit) else {
+                panic!("expected a reason");
+            };
+            assert_eq!(db.lits(clause), [lit, -2, -1]);
+            assert!(db.is_temporary(clause));
+        }
+    }
+}
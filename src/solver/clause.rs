@@ -0,0 +1,264 @@
+use crate::types::Lit;
+
+/// A reference to a clause stored in [`ClauseDb`], i.e., the offset
+/// of its header in the arena. References stay valid until the clause
+/// is deleted and the garbage is collected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClauseRef(u32);
+
+/// An entry in a watch list. If `blocker`, some other literal
+/// of the clause, is true, the clause is satisfied and
+/// propagation can skip it without dereferencing it.
+#[derive(Clone, Copy, Debug)]
+pub struct Watch {
+    pub clause: ClauseRef,
+    pub blocker: Lit,
+}
+
+impl Watch {
+    pub fn new(clause: ClauseRef, blocker: Lit) -> Self {
+        Self { clause, blocker }
+    }
+}
+
+// header layout
+const SIZE: usize = 0;
+const FLAGS: usize = 1;
+const LBD: usize = 2;
+const ACTIVITY: usize = 3;
+const HEADER_LEN: usize = 4;
+
+const LEARNT: Lit = 1;
+const DELETED: Lit = 2;
+const RELOCATED: Lit = 4;
+const VIVIFIED: Lit = 8;
+const TEMPORARY: Lit = 16;
+
+/// Clauses stored contiguously in a single buffer, each prefixed by a header
+/// holding its size, flags (learnt, deleted, vivified, temporary), LBD
+/// and activity.
+pub struct ClauseDb {
+    arena: Vec<Lit>,
+    wasted: usize,
+    temporary: Vec<ClauseRef>,
+}
+
+impl ClauseDb {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            arena: Vec::with_capacity(capacity),
+            wasted: 0,
+            temporary: vec![],
+        }
+    }
+
+    pub fn add(&mut self, lits: &[Lit], learnt: bool) -> ClauseRef {
+        let offset = self.arena.len();
+        assert!(offset <= u32::MAX as usize, "clause arena overflow");
+
+        self.arena.extend([
+            lits.len() as Lit,
+            if learnt { LEARNT } else { 0 },
+            0,
+            0.0f32.to_bits() as Lit,
+        ]);
+        self.arena.extend_from_slice(lits);
+
+        ClauseRef(offset as u32)
+    }
+
+    fn start(c: ClauseRef) -> usize {
+        c.0 as usize + HEADER_LEN
+    }
+
+    fn header(&self, c: ClauseRef, field: usize) -> Lit {
+        self.arena[c.0 as usize + field]
+    }
+
+    fn header_mut(&mut self, c: ClauseRef, field: usize) -> &mut Lit {
+        &mut self.arena[c.0 as usize + field]
+    }
+
+    pub fn len(&self, c: ClauseRef) -> usize {
+        self.header(c, SIZE) as usize
+    }
+
+    pub fn lits(&self, c: ClauseRef) -> &[Lit] {
+        let start = Self::start(c);
+        &self.arena[start..start + self.len(c)]
+    }
+
+    pub fn lits_mut(&mut self, c: ClauseRef) -> &mut [Lit] {
+        let start = Self::start(c);
+        let end = start + self.len(c);
+        &mut self.arena[start..end]
+    }
+
+    /// Drops all but the first `len` literals of `c`.
+    pub fn truncate(&mut self, c: ClauseRef, len: usize) {
+        let old_len = self.len(c);
+        debug_assert!(len <= old_len);
+        *self.header_mut(c, SIZE) = len as Lit;
+        self.wasted += old_len - len;
+    }
+
+    pub fn is_learnt(&self, c: ClauseRef) -> bool {
+        self.header(c, FLAGS) & LEARNT != 0
+    }
+
+    pub fn is_deleted(&self, c: ClauseRef) -> bool {
+        self.header(c, FLAGS) & DELETED != 0
+    }
+
+    /// Whether `c` was already vivified, i.e., shortened by propagating
+    /// the negations of its literals.
+    pub fn is_vivified(&self, c: ClauseRef) -> bool {
+        self.header(c, FLAGS) & VIVIFIED != 0
+    }
+
+    pub fn set_vivified(&mut self, c: ClauseRef) {
+        *self.header_mut(c, FLAGS) |= VIVIFIED;
+    }
+
+    /// Whether `c` only explains a propagation or conflict of a constraint
+    /// other than a clause. Such clauses are not watched and are deleted
+    /// once they are no longer reasons.
+    pub fn is_temporary(&self, c: ClauseRef) -> bool {
+        self.header(c, FLAGS) & TEMPORARY != 0
+    }
+
+    pub fn add_temporary(&mut self, lits: &[Lit]) -> ClauseRef {
+        let c = self.add(lits, false);
+        *self.header_mut(c, FLAGS) |= TEMPORARY;
+        self.temporary.push(c);
+        c
+    }
+
+    /// Deletes the temporary clauses that are not `locked`.
+    pub fn release_temporary(&mut self, mut locked: impl FnMut(&Self, ClauseRef) -> bool) {
+        let mut temporary = std::mem::take(&mut self.temporary);
+        temporary.retain(|&c| {
+            let keep = locked(self, c);
+            if !keep {
+                self.delete(c);
+            }
+            keep
+        });
+        self.temporary = temporary;
+    }
+
+    /// Marks `c` as deleted, its space is reclaimed by the next
+    /// [`ClauseDb::collect`].
+    pub fn delete(&mut self, c: ClauseRef) {
+        debug_assert!(!self.is_deleted(c));
+        *self.header_mut(c, FLAGS) |= DELETED;
+        self.wasted += HEADER_LEN + self.len(c);
+    }
+
+    /// Literal block distance, i.e., the number of distinct decision levels
+    /// in a learnt clause at the time it was derived.
+    pub fn lbd(&self, c: ClauseRef) -> usize {
+        self.header(c, LBD) as usize
+    }
+
+    pub fn set_lbd(&mut self, c: ClauseRef, lbd: usize) {
+        *self.header_mut(c, LBD) = lbd.min(Lit::MAX as usize) as Lit;
+    }
+
+    pub fn activity(&self, c: ClauseRef) -> f32 {
+        f32::from_bits(self.header(c, ACTIVITY) as u32)
+    }
+
+    pub fn set_activity(&mut self, c: ClauseRef, activity: f32) {
+        *self.header_mut(c, ACTIVITY) = activity.to_bits() as Lit;
+    }
+
+    /// The fraction of the arena occupied by deleted clauses.
+    pub fn wasted_ratio(&self) -> f64 {
+        self.wasted as f64 / self.arena.len().max(1) as f64
+    }
+
+    /// Moves `c` into `to` and returns its new reference.
+    /// Subsequent calls with the same `c` return the same reference.
+    pub fn relocate(&mut self, c: ClauseRef, to: &mut ClauseDb) -> ClauseRef {
+        if self.header(c, FLAGS) & RELOCATED != 0 {
+            return ClauseRef(self.header(c, ACTIVITY) as u32);
+        }
+        debug_assert!(!self.is_deleted(c));
+
+        let offset = to.arena.len();
+        let start = c.0 as usize;
+        to.arena
+            .extend_from_slice(&self.arena[start..Self::start(c) + self.len(c)]);
+        let new = ClauseRef(offset as u32);
+
+        *self.header_mut(c, FLAGS) |= RELOCATED;
+        *self.header_mut(c, ACTIVITY) = new.0 as Lit;
+        new
+    }
+
+    /// Creates an empty database to [`relocate`](ClauseDb::relocate)
+    /// the remaining clauses into, the temporary ones are moved right away.
+    pub fn collect(&mut self) -> ClauseDb {
+        let mut to = ClauseDb::with_capacity(self.arena.len() - self.wasted);
+        for c in std::mem::take(&mut self.temporary) {
+            let new = self.relocate(c, &mut to);
+            to.temporary.push(new);
+        }
+        to
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClauseDb;
+
+    #[test]
+    fn basic() {
+        let mut db = ClauseDb::with_capacity(0);
+        let c1 = db.add(&[1, -2, 3], false);
+        let c2 = db.add(&[-1, 2], true);
+        let c3 = db.add(&[4], true);
+        let c4 = db.add_temporary(&[-4]);
+        let c5 = db.add_temporary(&[5]);
+
+        assert_eq!(db.lits(c1), &[1, -2, 3]);
+        assert_eq!(db.lits(c2), &[-1, 2]);
+        assert!(!db.is_learnt(c1));
+        assert!(db.is_learnt(c2));
+
+        db.lits_mut(c1).swap(0, 2);
+        db.truncate(c3, 0);
+        db.set_lbd(c2, 2);
+        db.set_activity(c2, 1.5);
+        db.set_vivified(c2);
+        db.release_temporary(|_, c| c == c5);
+        assert!(db.is_deleted(c4));
+        assert_eq!(db.lits(c1), &[3, -2, 1]);
+        assert_eq!(db.lbd(c2), 2);
+        assert_eq!(db.activity(c2), 1.5);
+
+        db.delete(c1);
+        assert!(db.is_deleted(c1));
+        assert!(db.wasted_ratio() > 0.0);
+
+        let mut to = db.collect();
+        let new_c3 = db.relocate(c3, &mut to);
+        let new_c2 = db.relocate(c2, &mut to);
+        assert_eq!(db.relocate(c2, &mut to), new_c2);
+        let new_c5 = db.relocate(c5, &mut to);
+        let db = to;
+
+        assert_eq!(db.wasted_ratio(), 0.0);
+        assert_eq!(db.lits(new_c2), &[-1, 2]);
+        assert_eq!(db.lits(new_c3), &[]);
+        assert_eq!(db.lbd(new_c2), 2);
+        assert_eq!(db.activity(new_c2), 1.5);
+        assert!(db.is_vivified(new_c2));
+        assert!(!db.is_vivified(new_c3));
+        assert!(db.is_learnt(new_c3));
+        assert_eq!(db.lits(new_c5), &[5]);
+        assert!(db.is_temporary(new_c5));
+        assert!(!db.is_temporary(new_c2));
+    }
+}
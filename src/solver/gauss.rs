@@ -0,0 +1,594 @@
+use std::collections::HashMap;
+
+use crate::types::{to_var, Clause, Lit, Var};
+
+use super::{
+    assignment::Assignment,
+    clause::{ClauseDb, ClauseRef},
+    map::{var_map, VarMap},
+    Solver,
+};
+
+/// An XOR over `n` variables takes `2^(n-1)` clauses.
+const MAX_XOR_LEN: usize = 6;
+const CUT_LEN: usize = 4;
+const MAX_MATRIX_SIZE: usize = 1 << 26;
+
+/// The sum of the values of `vars` modulo 2 is `parity`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Xor {
+    pub vars: Vec<Var>,
+    pub parity: bool,
+}
+
+pub fn detect<'a>(clauses: impl Iterator<Item = &'a [Lit]>) -> Vec<Xor> {
+    // the sign patterns of the clauses over each set of variables,
+    // bit `i` is set if the `i`-th variable is negated
+    let mut patterns: HashMap<Vec<Var>, Vec<u32>> = HashMap::new();
+    for clause in clauses {
+        if !(3..=MAX_XOR_LEN).contains(&clause.len()) {
+            continue;
+        }
+        let mut lits = clause.to_vec();
+        lits.sort_unstable_by_key(|&lit| to_var(lit));
+        let vars: Vec<Var> = lits.iter().map(|&lit| to_var(lit)).collect();
+        if vars.windows(2).any(|pair| pair[0] == pair[1]) {
+            continue;
+        }
+        let pattern = lits
+            .iter()
+            .enumerate()
+            .filter(|(_, &lit)| lit < 0)
+            .map(|(i, _)| 1 << i)
+            .sum();
+        patterns.entry(vars).or_default().push(pattern);
+    }
+
+    let mut xors = vec![];
+    for (vars, mut patterns) in patterns {
+        let needed = 1 << (vars.len() - 1);
+        if patterns.len() < needed {
+            continue;
+        }
+        patterns.sort_unstable();
+        patterns.dedup();
+        for parity in [false, true] {
+            // a clause rules out the assignment setting exactly
+            // its negated variables to true
+            let count = patterns
+                .iter()
+                .filter(|pattern| (pattern.count_ones() % 2 == 1) != parity)
+                .count();
+            if count == needed {
+                xors.push(Xor {
+                    vars: vars.clone(),
+                    parity,
+                });
+            }
+        }
+    }
+    xors.sort_unstable_by(|a, b| (&a.vars, a.parity).cmp(&(&b.vars, b.parity)));
+    xors
+}
+
+/// Encodes that an odd number of the literals of `xor` is true, long
+/// constraints are cut into pieces connected by fresh variables.
+pub fn encode(xor: &[Lit], mut new_var: impl FnMut() -> Var) -> Vec<Clause> {
+    let mut parity = true;
+    let mut vars: Vec<Var> = xor
+        .iter()
+        .map(|&lit| {
+            parity ^= lit < 0;
+            to_var(lit)
+        })
+        .collect();
+    vars.sort_unstable();
+    // pairs of the same variable cancel out
+    let mut reduced: Vec<Var> = vec![];
+    for var in vars {
+        if reduced.last() == Some(&var) {
+            reduced.pop();
+        } else {
+            reduced.push(var);
+        }
+    }
+
+    let mut clauses = vec![];
+    while reduced.len() > CUT_LEN {
+        // the fresh variable is the sum of the cut off ones
+        let var = new_var();
+        let mut piece: Vec<Var> = reduced.drain(..CUT_LEN - 1).collect();
+        piece.push(var);
+        clauses.extend(parity_clauses(&piece, false));
+        reduced.push(var);
+    }
+    clauses.extend(parity_clauses(&reduced, parity));
+    clauses
+}
+
+pub(super) fn parity_clauses(vars: &[Var], parity: bool) -> Vec<Clause> {
+    (0..1u32 << vars.len())
+        .filter(|mask| (mask.count_ones() % 2 == 1) != parity)
+        .map(|mask| {
+            vars.iter()
+                .enumerate()
+                .map(|(i, &var)| {
+                    let lit = var as Lit;
+                    if mask & (1 << i) != 0 {
+                        -lit
+                    } else {
+                        lit
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+struct Row {
+    bits: Vec<u64>,
+    parity: bool,
+    /// The column occurring in no other row.
+    basic: usize,
+    watch: Option<usize>,
+}
+
+/// XOR constraints in reduced row echelon form, each row watches its basic
+/// column and one other column (Han and Jiang).
+pub struct Gauss {
+    rows: Vec<Row>,
+    vars: Vec<Var>,
+    columns: VarMap<Option<usize>>,
+    /// May contain outdated entries.
+    watches: Vec<Vec<usize>>,
+    inconsistent: bool,
+}
+
+impl Gauss {
+    /// Returns `None` if the matrix would be too large.
+    pub fn new(var_count: usize, xors: &[Xor]) -> Option<Self> {
+        let mut columns = var_map::<Option<usize>>(var_count);
+        let mut vars = vec![];
+        for &var in xors.iter().flat_map(|xor| &xor.vars) {
+            if columns[var].is_none() {
+                columns[var] = Some(vars.len());
+                vars.push(var);
+            }
+        }
+        if xors.len() * vars.len() > MAX_MATRIX_SIZE {
+            return None;
+        }
+
+        let width = vars.len().div_ceil(64);
+        let mut rows: Vec<(Vec<u64>, bool)> = xors
+            .iter()
+            .map(|xor| {
+                let mut bits = vec![0; width];
+                for &var in &xor.vars {
+                    toggle(&mut bits, columns[var].unwrap());
+                }
+                (bits, xor.parity)
+            })
+            .collect();
+
+        let mut inconsistent = false;
+        let mut basics = vec![];
+        for i in 0..rows.len() {
+            let Some(basic) = ones(&rows[i].0).next() else {
+                // all rows are reduced to zero or contain a basic column
+                inconsistent |= rows[i].1;
+                continue;
+            };
+            let (bits, parity) = rows[i].clone();
+            for (j, row) in rows.iter_mut().enumerate() {
+                if j != i && get(&row.0, basic) {
+                    add(&mut row.0, &bits);
+                    row.1 ^= parity;
+                }
+            }
+            basics.push((i, basic));
+        }
+
+        let mut gauss = Self {
+            rows: vec![],
+            watches: vec![vec![]; vars.len()],
+            vars,
+            columns,
+            inconsistent,
+        };
+        for (i, basic) in basics {
+            let (bits, parity) = std::mem::take(&mut rows[i]);
+            let watch = ones(&bits).find(|&col| col != basic);
+            let row = gauss.rows.len();
+            gauss.watches[basic].push(row);
+            if let Some(col) = watch {
+                gauss.watches[col].push(row);
+            }
+            gauss.rows.push(Row {
+                bits,
+                parity,
+                basic,
+                watch,
+            });
+        }
+        Some(gauss)
+    }
+
+    /// Returns `None` if the constraints are inconsistent.
+    pub fn facts(&self) -> Option<Vec<Lit>> {
+        if self.inconsistent {
+            return None;
+        }
+        let facts = self
+            .rows
+            .iter()
+            .filter(|row| row.watch.is_none())
+            .map(|row| {
+                let lit = self.vars[row.basic] as Lit;
+                if row.parity {
+                    lit
+                } else {
+                    -lit
+                }
+            })
+            .collect();
+        Some(facts)
+    }
+
+    pub fn propagate(
+        &mut self,
+        var: Var,
+        assignment: &mut Assignment,
+        db: &mut ClauseDb,
+    ) -> Option<ClauseRef> {
+        let col = self.columns[var]?;
+
+        let list = std::mem::take(&mut self.watches[col]);
+        // rows modified by elimination that need to be updated as well
+        let mut queue = vec![];
+        let mut conflict = None;
+        for i in list {
+            if !self.is_watching(i, col) {
+                // outdated
+                continue;
+            }
+            queue.push(i);
+            while let Some(j) = queue.pop() {
+                if conflict.is_none() {
+                    conflict = self.update(j, assignment, db, &mut queue);
+                } else {
+                    // the remaining rows still need valid watches,
+                    // elimination may have removed their watched columns
+                    self.rewatch(j, assignment, &mut queue);
+                }
+            }
+            if self.is_watching(i, col) {
+                self.watches[col].push(i);
+            }
+        }
+        self.watches[col].sort_unstable();
+        self.watches[col].dedup();
+
+        conflict
+    }
+
+    fn is_watching(&self, i: usize, col: usize) -> bool {
+        self.rows[i].basic == col || self.rows[i].watch == Some(col)
+    }
+
+    fn update(
+        &mut self,
+        i: usize,
+        assignment: &mut Assignment,
+        db: &mut ClauseDb,
+        queue: &mut Vec<usize>,
+    ) -> Option<ClauseRef> {
+        if self.rewatch(i, assignment, queue) {
+            self.settle(i, assignment, db)
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if the row has at most one unassigned column left.
+    fn rewatch(&mut self, i: usize, assignment: &Assignment, queue: &mut Vec<usize>) -> bool {
+        let row = &self.rows[i];
+        let mut watch = row
+            .watch
+            .filter(|&col| get(&row.bits, col) && self.is_unassigned(col, assignment));
+        if !self.is_unassigned(row.basic, assignment) {
+            let skip = [row.basic, watch.unwrap_or(usize::MAX)];
+            match self.find_unassigned(i, skip, assignment) {
+                Some(col) => self.pivot(i, col, queue),
+                None => {
+                    // the watched column is implied, it becomes basic so that
+                    // both watches are unassigned again on backtracking
+                    if let Some(col) = watch.take() {
+                        self.pivot(i, col, queue);
+                    }
+                }
+            }
+        }
+
+        let basic = self.rows[i].basic;
+        let watch = watch.or_else(|| self.find_unassigned(i, [basic, usize::MAX], assignment));
+        match watch {
+            Some(col) => {
+                self.set_watch(i, col);
+                !self.is_unassigned(basic, assignment)
+            }
+            None => {
+                // watch the column to be unassigned first on backtracking
+                let last = ones(&self.rows[i].bits)
+                    .filter(|&col| col != basic)
+                    .max_by_key(|&col| {
+                        let lit = self.vars[col] as Lit;
+                        (assignment.level(lit), assignment.position(lit))
+                    });
+                if let Some(col) = last {
+                    self.set_watch(i, col);
+                }
+                true
+            }
+        }
+    }
+
+    fn set_watch(&mut self, i: usize, col: usize) {
+        if self.rows[i].watch != Some(col) {
+            self.rows[i].watch = Some(col);
+            self.watches[col].push(i);
+        }
+    }
+
+    fn is_unassigned(&self, col: usize, assignment: &Assignment) -> bool {
+        assignment.eval(self.vars[col] as Lit).is_none()
+    }
+
+    fn find_unassigned(
+        &self,
+        i: usize,
+        skip: [usize; 2],
+        assignment: &Assignment,
+    ) -> Option<usize> {
+        ones(&self.rows[i].bits)
+            .find(|&col| !skip.contains(&col) && self.is_unassigned(col, assignment))
+    }
+
+    fn pivot(&mut self, i: usize, col: usize, queue: &mut Vec<usize>) {
+        let bits = self.rows[i].bits.clone();
+        let parity = self.rows[i].parity;
+        for (j, row) in self.rows.iter_mut().enumerate() {
+            if j != i && get(&row.bits, col) {
+                add(&mut row.bits, &bits);
+                row.parity ^= parity;
+                queue.push(j);
+            }
+        }
+        self.rows[i].basic = col;
+        self.watches[col].push(i);
+    }
+
+    fn settle(
+        &mut self,
+        i: usize,
+        assignment: &mut Assignment,
+        db: &mut ClauseDb,
+    ) -> Option<ClauseRef> {
+        // the implied literal is filled in at the first position
+        let mut lits = vec![0];
+        let mut implied = None;
+        let mut parity = self.rows[i].parity;
+        for col in ones(&self.rows[i].bits) {
+            let var = self.vars[col];
+            match assignment.eval(var as Lit) {
+                None => {
+                    debug_assert!(implied.is_none());
+                    implied = Some(var);
+                }
+                Some(value) => {
+                    parity ^= value;
+                    lits.push(if value { -(var as Lit) } else { var as Lit });
+                }
+            }
+        }
+
+        match implied {
+            Some(var) => {
+                lits[0] = if parity { var as Lit } else { -(var as Lit) };
+                let level = lits[1..]
+                    .iter()
+                    .map(|&lit| assignment.level(lit).unwrap())
+                    .max()
+                    .unwrap_or(0);
+                let c = db.add_temporary(&lits);
+                assignment.imply(lits[0], c, level);
+                None
+            }
+            None if parity => Some(db.add_temporary(&lits[1..])),
+            None => None,
+        }
+    }
+}
+
+impl Solver {
+    /// The clauses of the XOR constraints are kept.
+    pub(super) fn enable_gauss(&mut self) -> bool {
+        let xors = detect(self.clauses.iter().map(|&c| self.db.lits(c)));
+        if xors.is_empty() {
+            return true;
+        }
+        let Some(gauss) = Gauss::new(self.assignment.var_count(), &xors) else {
+            return true;
+        };
+        let Some(facts) = gauss.facts() else {
+            return false;
+        };
+
+        self.gauss = Some(gauss);
+        // the variables assigned so far are new to the matrix
+        self.gauss_head = 0;
+        facts.into_iter().all(|lit| self.add_fact(lit)) && self.propagate().is_none()
+    }
+}
+
+fn get(bits: &[u64], col: usize) -> bool {
+    bits[col / 64] & (1 << (col % 64)) != 0
+}
+
+fn toggle(bits: &mut [u64], col: usize) {
+    bits[col / 64] ^= 1 << (col % 64);
+}
+
+fn add(bits: &mut [u64], other: &[u64]) {
+    for (word, &other) in bits.iter_mut().zip(other) {
+        *word ^= other;
+    }
+}
+
+fn ones(bits: &[u64]) -> impl Iterator<Item = usize> + '_ {
+    bits.iter().enumerate().flat_map(|(i, &word)| {
+        let mut word = word;
+        std::iter::from_fn(move || {
+            (word != 0).then(|| {
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                64 * i + bit
+            })
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        solver::{
+            assignment::{Assignment, Reason},
+            clause::ClauseDb,
+        },
+        types::to_var,
+    };
+
+    use super::{detect, encode, Gauss, Xor};
+
+    #[test]
+    fn detection() {
+        let clauses: Vec<Vec<i32>> = vec![
+            // 1 + 2 + 3 = 1
+            vec![1, 2, 3],
+            vec![1, -2, -3],
+            vec![-1, 2, -3],
+            vec![-1, -2, 3],
+            // incomplete
+            vec![2, 3, 4],
+            vec![-2, -3, 4],
+            vec![-2, 3, -4],
+        ];
+        let xors = detect(clauses.iter().map(Vec::as_slice));
+        assert_eq!(
+            xors,
+            vec![Xor {
+                vars: vec![1, 2, 3],
+                parity: true
+            }]
+        );
+    }
+
+    #[test]
+    fn encoding() {
+        // 2 and -2 cancel out except for their parity
+        let xor = [1, -2, 3, 4, -5, 6, 2];
+        let mut var_count = 6;
+        let clauses = encode(&xor, || {
+            var_count += 1;
+            var_count
+        });
+        assert!(var_count > 6);
+
+        let value = |bits: u32, lit: i32| (bits >> (lit.abs() - 1) & 1 == 1) == (lit > 0);
+        for bits in 0..1 << 6 {
+            let odd = xor.iter().filter(|&&lit| value(bits, lit)).count() % 2 == 1;
+            // some values of the fresh variables satisfy the clauses
+            let satisfiable = (0..1 << (var_count - 6)).any(|fresh| {
+                let bits = bits | fresh << 6;
+                clauses
+                    .iter()
+                    .all(|clause| clause.iter().any(|&lit| value(bits, lit)))
+            });
+            assert_eq!(satisfiable, odd);
+        }
+
+        assert_eq!(encode(&[1, 1], || unreachable!()), vec![Vec::<i32>::new()]);
+        assert!(encode(&[1, -1], || unreachable!()).is_empty());
+    }
+
+    #[test]
+    fn propagation() {
+        // 1 + 2 + 3 = 1, 3 + 4 + 5 = 0
+        let xors = [
+            Xor {
+                vars: vec![1, 2, 3],
+                parity: true,
+            },
+            Xor {
+                vars: vec![3, 4, 5],
+                parity: false,
+            },
+        ];
+        let mut gauss = Gauss::new(5, &xors).unwrap();
+        assert_eq!(gauss.facts(), Some(vec![]));
+
+        let mut assignment = Assignment::new(5);
+        let mut db = ClauseDb::with_capacity(0);
+        for lit in [1, -2, 4] {
+            assignment.set(lit, Reason::Decision);
+            assert_eq!(gauss.propagate(to_var(lit), &mut assignment, &mut db), None);
+        }
+        // 1 + 2 + 3 = 1 implies 3 = 0, then 3 + 4 + 5 = 0 implies 5 = 1
+        assert_eq!(assignment.eval(-3), Some(true));
+        assert_eq!(assignment.eval(5), Some(true));
+        let Some(Reason::Propagation { clause }) = assignment.reason(5) else {
+            panic!("expected a reason");
+        };
+        assert_eq!(db.lits(clause)[0], 5);
+        assert!(db.is_temporary(clause));
+
+        let xors = [
+            Xor {
+                vars: vec![1, 2],
+                parity: true,
+            },
+            Xor {
+                vars: vec![1, 2],
+                parity: false,
+            },
+        ];
+        assert_eq!(Gauss::new(2, &xors).unwrap().facts(), None);
+    }
+
+    #[test]
+    fn out_of_order() {
+        // 1 + 2 + 3 = 1 with the basic column 1 watched together with 2
+        let xors = [Xor {
+            vars: vec![1, 2, 3],
+            parity: true,
+        }];
+        let mut gauss = Gauss::new(4, &xors).unwrap();
+        let mut assignment = Assignment::new(4);
+        let mut db = ClauseDb::with_capacity(0);
+        assignment.set(4, Reason::Decision);
+        assignment.set(3, Reason::Decision);
+        assert_eq!(gauss.propagate(3, &mut assignment, &mut db), None);
+        // 1 is assigned below the level of 3, so 2 is implied at level 2
+        let c = db.add(&[1, -4], false);
+        assignment.imply(1, c, 1);
+        assert_eq!(gauss.propagate(1, &mut assignment, &mut db), None);
+        assert_eq!(assignment.level(2), Some(2));
+
+        // 1 stays assigned, the row must still propagate once 3 is assigned
+        assignment.backtrack(2, |_| ());
+        assert_eq!(assignment.eval(1), Some(true));
+        assignment.set(3, Reason::Decision);
+        assert_eq!(gauss.propagate(3, &mut assignment, &mut db), None);
+        assert_eq!(assignment.eval(2), Some(true));
+    }
+}
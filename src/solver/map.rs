@@ -22,6 +22,17 @@ impl<T: Clone + Default> LitMap<T> {
     pub fn new(var_count: usize) -> Self {
         Self(vec![Default::default(); 2 * var_count + 1])
     }
+
+    /// Makes room for new variables up to `var_count`.
+    pub fn grow(&mut self, var_count: usize) {
+        self.0.resize(2 * var_count + 1, Default::default());
+    }
+}
+
+impl<T> LitMap<T> {
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.0.iter_mut()
+    }
 }
 
 impl<T> Index<Lit> for LitMap<T> {
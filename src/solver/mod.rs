@@ -1,37 +1,102 @@
 mod activity;
 mod assignment;
-mod map;
+mod card;
+mod clause;
+pub(crate) mod gauss;
+pub(crate) mod map;
+mod probe;
+pub(crate) mod random;
+mod rephase;
 mod restart;
+pub(crate) mod subsume;
+mod vivify;
+mod walk;
 
-use std::iter::Peekable;
+use std::{cmp::Reverse, iter::Peekable};
 
 use crate::{
     solver::assignment::Reason,
-    types::{to_var, Clause, Lit, Problem, Proof, ProofStep, Solution},
+    types::{to_var, Clause, Lit, Problem, Proof, ProofStep, Solution, Var},
 };
 
 use self::{
     activity::{ClauseTracker, Evsids},
     assignment::Assignment,
-    map::LitMap,
+    card::Cards,
+    clause::{ClauseDb, ClauseRef, Watch},
+    gauss::Gauss,
+    map::{var_map, LitMap, VarMap},
+    random::Rng,
+    rephase::{Rephase, Schedule},
     restart::Luby,
+    subsume::Action,
 };
 
+/// Conflicts before the first rephase, the intervals grow arithmetically.
+const REPHASE_INTERVAL: usize = 1000;
+const WALK_EFFORT: usize = 20;
+/// Learnt clauses with LBD up to this value are never pruned.
+const GLUE_LBD: usize = 2;
+/// Backjumps over more levels only undo the last level.
+const CHRONO_LEVELS: usize = 100;
+const INPROCESS_INTERVAL: usize = 5000;
+
+#[derive(Clone, Copy)]
+pub enum Polarity {
+    Negative,
+    Positive,
+    Random,
+}
+
+#[derive(Clone, Default)]
+pub struct Options {
+    /// If unset, the initial order and phases are not perturbed.
+    pub seed: Option<u64>,
+    pub random_freq: f64,
+    pub cardinality: bool,
+    pub no_gauss: bool,
+}
+
 pub struct Solver {
-    clauses: Vec<Clause>,
-    min_clause_count: usize,
+    /// The other variables encode XOR constraints.
+    original_var_count: usize,
+    db: ClauseDb,
+    clauses: Vec<ClauseRef>,
+    learnts: Vec<ClauseRef>,
     max_learnt: f64,
+    root_facts: usize,
 
     assignment: Assignment,
 
-    watched: LitMap<Vec<usize>>,
+    watched: LitMap<Vec<Watch>>,
+    /// The blocker is the other literal of the clause.
+    binary: LitMap<Vec<Watch>>,
     prop_head: usize,
+    binary_head: usize,
+    cards: Option<Cards>,
+    card_head: usize,
+    cardinality: bool,
+    gauss: Option<Gauss>,
+    gauss_head: usize,
+    no_gauss: bool,
 
     evsids: Evsids,
     clause_tracker: ClauseTracker,
+    seen: VarMap<bool>,
+    poisoned: VarMap<bool>,
 
     conflicts: usize,
     restart_threshold: Peekable<Luby>,
+    rephase_schedule: Schedule,
+    rephase_count: usize,
+    rephase_conflicts: usize,
+    inprocess_count: usize,
+    inprocess_conflicts: usize,
+    probe_next: Var,
+    vivify_pending: bool,
+
+    rng: Rng,
+    random_freq: f64,
 
     proof: Option<Proof>,
 }
@@ -41,226 +106,740 @@ impl Solver {
         Self::create(problem, None)
     }
 
+    /// # Panics
+    ///
+    /// Panics if the problem has XOR constraints, whose encoding
+    /// is not part of the problem the proof is checked against.
     pub fn with_proof(problem: Problem) -> Self {
+        assert!(
+            problem.xors.is_empty(),
+            "incompatible input, proofs cannot refer to XOR constraints"
+        );
         Self::create(problem, Some(vec![]))
     }
 
+    /// Also perturbs the initial variable order and the phases not set
+    /// explicitly.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+        self.evsids.perturb(&mut self.rng);
+        self.assignment.perturb_phases(&mut self.rng);
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `freq` is not between 0 and 1.
+    pub fn set_random_freq(&mut self, freq: f64) {
+        assert!((0.0..=1.0).contains(&freq));
+        self.random_freq = freq;
+    }
+
+    /// Variables are assigned negatively by default.
+    pub fn set_initial_polarity(&mut self, polarity: Polarity) {
+        for var in 1..=self.assignment.var_count() {
+            let phase = match polarity {
+                Polarity::Negative => false,
+                Polarity::Positive => true,
+                Polarity::Random => self.rng.next_bool(),
+            };
+            self.assignment.set_original_phase(var, phase);
+        }
+    }
+
+    pub fn set_phase(&mut self, lit: Lit) {
+        self.assignment
+            .set_original_phase(to_var(lit), lit.is_positive());
+    }
+
+    /// Non-decision variables left unassigned by propagation
+    /// are decided last.
+    pub fn set_decision_var(&mut self, var: Var, decision: bool) {
+        self.evsids.set_decision(var, decision);
+    }
+
+    /// `amount` is measured in conflict bumps.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resulting activity is negative or not finite.
+    pub fn bump_activity(&mut self, var: Var, amount: f64) {
+        self.evsids.bump(var, amount);
+    }
+
+    /// # Panics
+    ///
+    /// Panics if an activity is negative or not finite.
+    pub fn seed_activity(&mut self, activity: impl IntoIterator<Item = (Var, f64)>) {
+        for (var, amount) in activity {
+            self.evsids.set_activity(var, amount);
+        }
+    }
+
+    pub fn set_options(&mut self, options: &Options) {
+        if let Some(seed) = options.seed {
+            self.set_seed(seed);
+        }
+        self.set_random_freq(options.random_freq);
+        self.cardinality = options.cardinality;
+        self.no_gauss = options.no_gauss;
+    }
+
     fn create(problem: Problem, proof: Option<Proof>) -> Self {
-        let Problem { var_count, clauses } = problem;
+        let Problem {
+            var_count: original_var_count,
+            mut clauses,
+            xors,
+        } = problem;
+        let mut var_count = original_var_count;
+        for xor in &xors {
+            clauses.extend(gauss::encode(xor, || {
+                var_count += 1;
+                var_count
+            }));
+        }
 
         let mut solver = Solver {
+            original_var_count,
+            db: ClauseDb::with_capacity(clauses.iter().map(Vec::len).sum()),
             clauses: Vec::with_capacity(clauses.len()),
-            min_clause_count: clauses.len(),
+            learnts: vec![],
             max_learnt: clauses.len() as f64 / 3.0,
+            root_facts: 0,
             assignment: Assignment::new(var_count),
-            watched: LitMap::<Vec<usize>>::new(var_count),
+            watched: LitMap::<Vec<Watch>>::new(var_count),
+            binary: LitMap::<Vec<Watch>>::new(var_count),
             prop_head: 0,
+            binary_head: 0,
+            cards: None,
+            card_head: 0,
+            cardinality: false,
+            gauss: None,
+            gauss_head: 0,
+            no_gauss: false,
             evsids: Evsids::new(var_count),
-            clause_tracker: ClauseTracker::new(clauses.len()),
+            clause_tracker: ClauseTracker::new(),
+            seen: var_map(var_count),
+            poisoned: var_map(var_count),
             conflicts: 0,
             restart_threshold: Luby::new(4096).peekable(),
+            rephase_schedule: Schedule::new(),
+            rephase_count: 1,
+            rephase_conflicts: 0,
+            inprocess_count: 1,
+            inprocess_conflicts: 0,
+            probe_next: 1,
+            vivify_pending: false,
+            rng: Rng::new(0),
+            random_freq: 0.0,
             proof,
         };
 
         for mut clause in clauses {
             clause.sort();
             clause.dedup();
-            solver.add(clause);
+            solver.add(&clause, false);
         }
 
         solver
     }
 
-    fn add(&mut self, clause: Clause) -> usize {
-        let i = self.clauses.len();
-        if let [lit0, lit1, ..] = clause[..] {
-            self.watched[lit0].push(i);
-            self.watched[lit1].push(i);
+    fn add(&mut self, clause: &[Lit], learnt: bool) -> ClauseRef {
+        let c = self.db.add(clause, learnt);
+        self.watch(c);
+        if learnt {
+            self.learnts.push(c);
+        } else {
+            self.clauses.push(c);
         }
-        self.clauses.push(clause);
-        self.clause_tracker.add();
-        i
+        c
     }
 
-    fn remove(&mut self, i_clause: usize) -> Option<Clause> {
-        for &lit in self.assignment.trail() {
-            let reason = self.assignment.reason(lit).unwrap();
-            if let Reason::Propagation { i_clause: i } = reason {
-                if i == i_clause {
-                    // removal blocked
-                    return None;
-                }
+    fn watch(&mut self, c: ClauseRef) {
+        match *self.db.lits(c) {
+            [lit0, lit1] => {
+                self.binary[lit0].push(Watch::new(c, lit1));
+                self.binary[lit1].push(Watch::new(c, lit0));
+            }
+            [lit0, lit1, ..] => {
+                self.watched[lit0].push(Watch::new(c, lit1));
+                self.watched[lit1].push(Watch::new(c, lit0));
             }
+            _ => (),
         }
+    }
+
+    fn locked(&self, c: ClauseRef) -> bool {
+        is_reason(&self.db, &self.assignment, c)
+    }
 
-        for &lit in &self.clauses[i_clause] {
-            self.watched[lit].retain(|&i| i != i_clause);
+    /// The watches need to be cleaned up afterwards, see [`Solver::prune`].
+    fn remove(&mut self, c: ClauseRef) -> bool {
+        if self.locked(c) {
+            // removal blocked
+            return false;
         }
 
-        let i_last = self.clauses.len() - 1;
-        for &lit in &self.clauses[i_last] {
-            for i in &mut self.watched[lit] {
-                if *i == i_last {
-                    *i = i_clause;
-                }
-            }
+        if let Some(proof) = self.proof.as_mut() {
+            proof.push((ProofStep::Delete, self.db.lits(c).to_vec()));
         }
-        self.assignment.rename_clause(i_last, i_clause);
+        self.db.delete(c);
+        true
+    }
 
-        self.clause_tracker.swap_remove(i_clause);
-        Some(self.clauses.swap_remove(i_clause))
+    fn release_reasons(&mut self) {
+        let assignment = &self.assignment;
+        self.db
+            .release_temporary(|db, c| is_reason(db, assignment, c));
     }
 
     fn prune(&mut self) {
-        let pivot = self.clause_tracker.select_pivot(self.min_clause_count);
+        let pivot = self.clause_tracker.select_pivot(&self.db, &self.learnts);
 
-        let mut i = self.min_clause_count;
-        while i < self.clauses.len() {
-            if self.clause_tracker.get_activity(i) < pivot {
-                let removed = self.remove(i);
-                if let Some(clause) = removed {
-                    if let Some(proof) = self.proof.as_mut() {
-                        proof.push((ProofStep::Delete, clause));
+        let mut learnts = std::mem::take(&mut self.learnts);
+        learnts.retain(|&c| {
+            let remove = self.db.lbd(c) > GLUE_LBD && self.db.activity(c) < pivot;
+            !(remove && self.remove(c))
+        });
+        self.learnts = learnts;
+
+        for watched in self.watched.iter_mut().chain(self.binary.iter_mut()) {
+            watched.retain(|watch| !self.db.is_deleted(watch.clause));
+        }
+
+        if self.db.wasted_ratio() > 0.5 {
+            self.collect_garbage();
+        }
+    }
+
+    /// Must be called after propagation.
+    fn simplify_root(&mut self) {
+        let trail = self.assignment.trail();
+        if self.assignment.last_level() > 0 || trail.len() == self.root_facts {
+            return;
+        }
+
+        if let Some(proof) = self.proof.as_mut() {
+            // the reasons are about to be removed,
+            // so the facts are added explicitly
+            for &lit in &trail[self.root_facts..] {
+                proof.push((ProofStep::Add, vec![lit]));
+            }
+        }
+        self.root_facts = trail.len();
+        self.assignment.forget_reasons();
+
+        let mut new_binary = vec![];
+        for learnt in [false, true] {
+            let mut list = std::mem::take(match learnt {
+                false => &mut self.clauses,
+                true => &mut self.learnts,
+            });
+            list.retain(|&c| {
+                let lits = self.db.lits(c);
+                if lits
+                    .iter()
+                    .any(|&lit| self.assignment.eval(lit) == Some(true))
+                {
+                    let removed = self.remove(c);
+                    debug_assert!(removed);
+                    return false;
+                }
+                if lits.iter().any(|&lit| self.assignment.eval(lit).is_some()) {
+                    // the watched literals are unassigned, so they stay in place
+                    debug_assert!(lits[..2]
+                        .iter()
+                        .all(|&lit| self.assignment.eval(lit).is_none()));
+                    let lits: Clause = lits
+                        .iter()
+                        .copied()
+                        .filter(|&lit| self.assignment.eval(lit).is_none())
+                        .collect();
+                    self.strengthen(c, &lits);
+                    if self.db.len(c) == 2 {
+                        new_binary.push(c);
                     }
-                    continue;
                 }
+                true
+            });
+            match learnt {
+                false => self.clauses = list,
+                true => self.learnts = list,
             }
-            i += 1;
+        }
+
+        let db = &self.db;
+        for watched in self.watched.iter_mut() {
+            watched.retain(|watch| !db.is_deleted(watch.clause) && db.len(watch.clause) > 2);
+        }
+        for watched in self.binary.iter_mut() {
+            watched.retain(|watch| !db.is_deleted(watch.clause));
+        }
+        for c in new_binary {
+            self.watch(c);
+        }
+
+        if self.db.wasted_ratio() > 0.5 {
+            self.collect_garbage();
         }
     }
 
-    fn propagate(&mut self) -> Option<usize> {
-        while let Some(lit) = self.assignment.trail().get(self.prop_head) {
-            let lit = -lit;
+    /// The watches are not updated.
+    fn strengthen(&mut self, c: ClauseRef, lits: &[Lit]) {
+        let old = self.db.lits(c).to_vec();
+        self.db.lits_mut(c)[..lits.len()].copy_from_slice(lits);
+        self.db.truncate(c, lits.len());
 
-            let mut i = 0;
-            'clause: while i < self.watched[lit].len() {
-                let c = self.watched[lit][i];
-                let clause = &mut self.clauses[c];
+        if let Some(proof) = self.proof.as_mut() {
+            proof.push((ProofStep::Add, lits.to_vec()));
+            proof.push((ProofStep::Delete, old));
+        }
+    }
+
+    fn restart_root(&mut self) -> bool {
+        if self.assignment.last_level() > 0 {
+            self.backtrack(1);
+        }
+        // facts kept by chronological backtracking might not be propagated
+        if self.propagate().is_some() {
+            return false;
+        }
+        self.simplify_root();
+        true
+    }
 
-                // Uses "implicit" watches, i.e., the two watched literals
-                // are always stored at index 0 and 1. (Borrowed from ministat.)
+    fn inprocess(&mut self) -> bool {
+        self.restart_root() && self.probe() && self.subsume()
+    }
 
-                if clause[1] != lit {
-                    clause.swap(0, 1);
+    /// Expects no clause to contain assigned literals.
+    fn subsume(&mut self) -> bool {
+        let refs: Vec<ClauseRef> = self.clauses.iter().chain(&self.learnts).copied().collect();
+        let lits: Vec<&[Lit]> = refs.iter().map(|&c| self.db.lits(c)).collect();
+        let redundant: Vec<bool> = refs.iter().map(|&c| self.db.is_learnt(c)).collect();
+        let actions = subsume::subsume(self.assignment.var_count(), &lits, &redundant);
+        if actions.is_empty() {
+            return true;
+        }
+
+        for action in actions {
+            match action {
+                Action::Remove(i) => {
+                    let removed = self.remove(refs[i]);
+                    debug_assert!(removed);
+                }
+                Action::Strengthen(i, lit) => {
+                    let lits: Clause = self
+                        .db
+                        .lits(refs[i])
+                        .iter()
+                        .copied()
+                        .filter(|&other| other != lit)
+                        .collect();
+                    self.strengthen(refs[i], &lits);
                 }
-                debug_assert_eq!(clause[1], lit);
+            }
+        }
 
-                for j in 0..clause.len() {
-                    match self.assignment.eval(clause[j]) {
-                        Some(true) => {
-                            i += 1;
-                            continue 'clause;
-                        }
-                        None if j != 0 => {
-                            clause.swap(1, j);
-                            // TODO: ensure distinct literals in each clause?
-                            debug_assert_ne!(clause[0], clause[1]);
+        // any literals can be watched since none are assigned
+        let db = &self.db;
+        self.clauses.retain(|&c| !db.is_deleted(c));
+        self.learnts.retain(|&c| !db.is_deleted(c));
+        for watched in self.watched.iter_mut().chain(self.binary.iter_mut()) {
+            watched.clear();
+        }
+        let mut units = vec![];
+        for c in self.clauses.clone().into_iter().chain(self.learnts.clone()) {
+            match *self.db.lits(c) {
+                [] => return false,
+                [lit] => units.push((lit, c)),
+                _ => self.watch(c),
+            }
+        }
 
-                            self.watched[lit].swap_remove(i);
-                            debug_assert!(!self.watched[clause[1]].contains(&c));
-                            self.watched[clause[1]].push(c);
+        for (lit, c) in units {
+            match self.assignment.eval(lit) {
+                None => self.assignment.set(lit, Reason::Propagation { clause: c }),
+                Some(false) => return false,
+                Some(true) => (),
+            }
+        }
+        if self.propagate().is_some() {
+            return false;
+        }
+        self.simplify_root();
 
-                            continue 'clause;
-                        }
-                        _ => (),
-                    }
-                }
+        if self.db.wasted_ratio() > 0.5 {
+            self.collect_garbage();
+        }
+        true
+    }
 
-                if self.assignment.eval(clause[0]).is_none() {
-                    // unit clause
-                    let unit_lit = clause[0];
-                    self.assignment
-                        .set(unit_lit, Reason::Propagation { i_clause: c });
-                } else {
-                    // conflict
-                    return Some(c);
+    fn collect_garbage(&mut self) {
+        let db = &mut self.db;
+        let mut to = db.collect();
+
+        for watched in self.watched.iter_mut().chain(self.binary.iter_mut()) {
+            for watch in watched.iter_mut() {
+                watch.clause = db.relocate(watch.clause, &mut to);
+            }
+        }
+        self.assignment.relocate(|c| db.relocate(c, &mut to));
+        for c in self.clauses.iter_mut().chain(self.learnts.iter_mut()) {
+            *c = db.relocate(*c, &mut to);
+        }
+
+        self.db = to;
+    }
+
+    /// Binary clauses first, then cardinality constraints, long clauses
+    /// and XOR constraints.
+    fn propagate(&mut self) -> Option<ClauseRef> {
+        loop {
+            let trail = self.assignment.trail();
+            if let Some(&lit) = trail.get(self.binary_head) {
+                if let Some(conflict) = self.propagate_binary(-lit) {
+                    return Some(conflict);
+                }
+                self.binary_head += 1;
+            } else if let Some(&lit) = trail.get(self.card_head).filter(|_| self.cards.is_some()) {
+                let cards = self.cards.as_mut().unwrap();
+                if let Some(conflict) = cards.propagate(lit, &mut self.assignment, &mut self.db) {
+                    return Some(conflict);
+                }
+                self.card_head += 1;
+            } else if let Some(&lit) = trail.get(self.prop_head) {
+                if let Some(conflict) = self.propagate_long(-lit) {
+                    return Some(conflict);
                 }
+                self.prop_head += 1;
+            } else if let Some(&lit) = trail.get(self.gauss_head).filter(|_| self.gauss.is_some()) {
+                let gauss = self.gauss.as_mut().unwrap();
+                if let Some(conflict) =
+                    gauss.propagate(to_var(lit), &mut self.assignment, &mut self.db)
+                {
+                    return Some(conflict);
+                }
+                self.gauss_head += 1;
+            } else {
+                return None;
+            }
+        }
+    }
+
+    fn propagate_binary(&mut self, lit: Lit) -> Option<ClauseRef> {
+        let level = self.assignment.level(lit).unwrap();
+        for i in 0..self.binary[lit].len() {
+            let Watch { clause: c, blocker } = self.binary[lit][i];
+            match self.assignment.eval(blocker) {
+                Some(true) => (),
+                None => self.assignment.imply(blocker, c, level),
+                Some(false) => return Some(c),
+            }
+        }
+        None
+    }
 
+    fn propagate_long(&mut self, lit: Lit) -> Option<ClauseRef> {
+        let mut i = 0;
+        'clause: while i < self.watched[lit].len() {
+            let Watch { clause: c, blocker } = self.watched[lit][i];
+            if self.assignment.eval(blocker) == Some(true) {
+                // satisfied, no need to look at the clause
                 i += 1;
+                continue;
+            }
+
+            let clause = self.db.lits_mut(c);
+
+            // Uses "implicit" watches, i.e., the two watched literals
+            // are always stored at index 0 and 1. (Borrowed from ministat.)
+
+            if clause[1] != lit {
+                clause.swap(0, 1);
+            }
+            debug_assert_eq!(clause[1], lit);
+
+            for j in 0..clause.len() {
+                match self.assignment.eval(clause[j]) {
+                    Some(true) => {
+                        self.watched[lit][i].blocker = clause[j];
+                        i += 1;
+                        continue 'clause;
+                    }
+                    None if j != 0 => {
+                        clause.swap(1, j);
+                        // TODO: ensure distinct literals in each clause?
+                        debug_assert_ne!(clause[0], clause[1]);
+
+                        self.watched[lit].swap_remove(i);
+                        debug_assert!(!self.watched[clause[1]]
+                            .iter()
+                            .any(|watch| watch.clause == c));
+                        self.watched[clause[1]].push(Watch::new(c, clause[0]));
+
+                        continue 'clause;
+                    }
+                    _ => (),
+                }
+            }
+
+            if self.assignment.eval(clause[0]).is_none() {
+                // unit clause
+                let unit_lit = clause[0];
+                let mut level = self.assignment.level(lit).unwrap();
+                if level < self.assignment.last_level() {
+                    level = clause[2..]
+                        .iter()
+                        .map(|&lit| self.assignment.level(lit).unwrap())
+                        .fold(level, usize::max);
+                }
+                self.assignment.imply(unit_lit, c, level);
+            } else {
+                // conflict
+                return Some(c);
             }
 
-            self.prop_head += 1;
+            i += 1;
         }
 
         None
     }
 
-    // based on minisat's basic clause minimization
+    // based on minisat's recursive clause minimization,
+    // expects `seen` to be set for the literals in `learnt`
     fn simplify(&mut self, learnt: &mut Clause) {
+        // a literal can only be implied by the others if all the decisions
+        // it depends on are at the levels present in the clause
+        let levels = learnt[1..].iter().fold(0, |levels, &lit| {
+            levels | abstract_level(self.assignment.level(lit).unwrap())
+        });
+
+        let mut stack = vec![];
+        let mut marked = vec![];
         let mut i = 1;
         while i < learnt.len() {
-            if let Some(Reason::Propagation { i_clause }) = self.assignment.reason(learnt[i]) {
-                let remove = self.clauses[i_clause].iter().all(|&lit| {
-                    learnt.contains(&lit)
-                        || learnt.contains(&-lit)
-                        || self.assignment.level(lit) == Some(0)
-                });
-                if remove {
-                    learnt.swap_remove(i);
-                    continue;
-                }
+            if self.redundant(learnt[i], levels, &mut stack, &mut marked) {
+                learnt.swap_remove(i);
+            } else {
+                i += 1;
             }
-            i += 1;
+        }
+
+        for var in marked {
+            self.seen[var] = false;
+            self.poisoned[var] = false;
         }
     }
 
-    fn analyze(&mut self, i_conflict: usize) -> (Clause, usize) {
-        let mut learnt = self.clauses[i_conflict].clone();
-        let last_level = self.assignment.last_level();
+    /// Whether `lit` is implied by the literals marked as `seen`,
+    /// the visited variables are pushed to `marked`.
+    fn redundant(
+        &mut self,
+        lit: Lit,
+        levels: u64,
+        stack: &mut Vec<(Lit, usize)>,
+        marked: &mut Vec<Var>,
+    ) -> bool {
+        if self.assignment.reason(lit) == Some(Reason::Decision) {
+            return false;
+        }
 
-        self.clause_tracker.touch(i_conflict);
+        // literals whose reasons are being explored together with
+        // the position of the next literal to check
+        stack.clear();
+        stack.push((lit, 0));
+        while let Some(&(lit, i)) = stack.last() {
+            let Some(Reason::Propagation { clause }) = self.assignment.reason(lit) else {
+                unreachable!();
+            };
 
-        let mut i_trail = self.assignment.trail().len();
-        let i_assert = loop {
-            // FIXME: which vars should be touched?
-            for &lit in &learnt {
-                self.evsids.touch(to_var(lit));
+            if i == self.db.len(clause) {
+                // all the antecedents are implied
+                stack.pop();
+                if !stack.is_empty() {
+                    self.seen[to_var(lit)] = true;
+                    marked.push(to_var(lit));
+                }
+                continue;
+            }
+            stack.last_mut().unwrap().1 += 1;
+
+            let other = self.db.lits(clause)[i];
+            let var = to_var(other);
+            let level = self.assignment.level(other).unwrap();
+            if var == to_var(lit) || self.seen[var] || level == 0 {
+                continue;
             }
 
-            let mut iter = learnt
+            let expandable = !self.poisoned[var]
+                && self.assignment.reason(other) != Some(Reason::Decision)
+                && levels & abstract_level(level) != 0;
+            if !expandable {
+                for &(lit, _) in &stack[1..] {
+                    self.poisoned[to_var(lit)] = true;
+                    marked.push(to_var(lit));
+                }
+                return false;
+            }
+            stack.push((other, 0));
+        }
+
+        true
+    }
+
+    /// Replaces the literals of `learnt` on each level by their block UIP.
+    fn shrink(&mut self, learnt: &mut Clause, marked: &mut Vec<Lit>) {
+        // group the literals by level, the variables marked at one level
+        // never appear in the reasons at lower levels
+        learnt[1..].sort_unstable_by_key(|&lit| Reverse(self.assignment.level(lit).unwrap()));
+        // the minimized literals are resolved too, they are implied anyway
+        let mut blocks: Vec<(usize, usize)> = marked
+            .iter()
+            .map(|&lit| {
+                let level = self.assignment.level(lit).unwrap();
+                (level, self.assignment.position(lit).unwrap())
+            })
+            .collect();
+        blocks.sort_unstable_by_key(|&block| Reverse(block));
+
+        let mut shrunk = vec![learnt[0]];
+        let mut i = 1;
+        while i < learnt.len() {
+            let level = self.assignment.level(learnt[i]).unwrap();
+            let len = learnt[i..]
                 .iter()
-                .enumerate()
-                .filter(|(_, &lit)| self.assignment.level(lit).unwrap() >= last_level);
-            // FIXME: can it be None?
-            let (i, _) = iter.next().unwrap();
-            if iter.next().is_none() {
-                break i;
+                .take_while(|&&lit| self.assignment.level(lit) == Some(level))
+                .count();
+
+            // the literals at `level` in `blocks`, the first one is the latest on the trail
+            let start = blocks.partition_point(|&(l, _)| l > level);
+            let pending = blocks[start..].partition_point(|&(l, _)| l == level);
+            let last = blocks[start].1;
+
+            match (len > 1)
+                .then(|| self.block_uip(level, pending, last, marked))
+                .flatten()
+            {
+                Some(uip) => shrunk.push(-uip),
+                None => shrunk.extend_from_slice(&learnt[i..i + len]),
             }
+            i += len;
+        }
 
-            i_trail -= 1;
-            let on_lit = self.assignment.trail()[i_trail];
+        *learnt = shrunk;
+    }
 
-            let i_reason = match self.assignment.reason(on_lit).unwrap() {
-                Reason::Propagation { i_clause } => i_clause,
-                Reason::Decision => unreachable!(),
+    fn block_uip(
+        &mut self,
+        level: usize,
+        mut pending: usize,
+        last: usize,
+        marked: &mut Vec<Lit>,
+    ) -> Option<Lit> {
+        for i in (0..=last).rev() {
+            let lit = self.assignment.trail()[i];
+            if !self.seen[to_var(lit)] || self.assignment.level(lit) != Some(level) {
+                continue;
+            }
+            if pending == 1 {
+                return Some(lit);
+            }
+            pending -= 1;
+
+            let Some(Reason::Propagation { clause }) = self.assignment.reason(lit) else {
+                unreachable!();
             };
-            let reason = &self.clauses[i_reason];
-            debug_assert!(reason.contains(&on_lit));
+            for &other in self.db.lits(clause) {
+                let var = to_var(other);
+                if var == to_var(lit) || self.seen[var] {
+                    continue;
+                }
+                match self.assignment.level(other).unwrap() {
+                    0 => {}
+                    l if l == level => {
+                        self.seen[var] = true;
+                        marked.push(other);
+                        pending += 1;
+                    }
+                    _ => return None,
+                }
+            }
+        }
 
-            self.clause_tracker.touch(i_reason);
+        unreachable!()
+    }
 
-            let len_before = learnt.len();
-            learnt.retain(|&lit| lit != -on_lit);
-            if learnt.len() != len_before {
-                // learnt contained -on_lit, finish the resolution
-                learnt.extend(reason.iter().filter(|&&lit| lit != on_lit));
-                // need to dedup to correctly determine #lits at a given level
-                learnt.sort();
-                learnt.dedup();
+    /// Must not be called at level 0.
+    fn analyze(&mut self, conflict: ClauseRef) -> (Clause, usize) {
+        let last_level = self.assignment.last_level();
+        debug_assert!(last_level > 0);
+
+        // the asserting literal is filled in at the end
+        let mut learnt = vec![0];
+        // #literals at the last level still to be resolved
+        let mut pending = 0;
+
+        let mut c = conflict;
+        let mut resolved: Option<Lit> = None;
+        let mut i_trail = self.assignment.trail().len();
+        loop {
+            self.clause_tracker.touch(&mut self.db, c);
+
+            for i in 0..self.db.len(c) {
+                let lit = self.db.lits(c)[i];
+                let var = to_var(lit);
+                if Some(lit) == resolved || self.seen[var] {
+                    continue;
+                }
+
+                let level = self.assignment.level(lit).unwrap();
+                if level == 0 {
+                    // root-level facts are left out
+                    continue;
+                }
+
+                self.seen[var] = true;
+                self.evsids.touch(var);
+                if level >= last_level {
+                    pending += 1;
+                } else {
+                    learnt.push(lit);
+                }
+            }
+
+            // find the next literal to resolve on, the trail may contain
+            // literals from lower levels after those at the last one
+            let lit = loop {
+                i_trail -= 1;
+                let lit = self.assignment.trail()[i_trail];
+                if self.seen[to_var(lit)] && self.assignment.level(lit) == Some(last_level) {
+                    break lit;
+                }
+            };
+
+            pending -= 1;
+            if pending == 0 {
+                learnt[0] = -lit;
+                break;
             }
-        };
 
-        learnt.swap(0, i_assert);
+            self.seen[to_var(lit)] = false;
+            resolved = Some(lit);
+            c = match self.assignment.reason(lit).unwrap() {
+                Reason::Propagation { clause } => clause,
+                Reason::Decision => unreachable!(),
+            };
+        }
 
+        let mut marked = learnt.clone();
         self.simplify(&mut learnt);
+        self.shrink(&mut learnt, &mut marked);
+        for lit in marked {
+            self.seen[to_var(lit)] = false;
+        }
 
         let backtrack_level = if learnt.len() == 1 {
-            if self.assignment.last_level() > 0 {
-                1
-            } else {
-                self.assignment.eval(learnt[0]).unwrap() as usize
-            }
+            1
         } else {
             let (i_max, _) = learnt[1..]
                 .iter()
@@ -273,19 +852,152 @@ impl Solver {
         };
 
         self.evsids.rescale();
-        self.clause_tracker.rescale();
+        self.clause_tracker.rescale(&mut self.db, &self.learnts);
 
         (learnt, backtrack_level)
     }
 
+    /// The highest level in the conflict and the number of its literals,
+    /// long clauses are reordered to watch the highest levels.
+    fn conflict_level(&mut self, conflict: ClauseRef) -> (usize, usize) {
+        let watched = !self.db.is_temporary(conflict);
+        let assignment = &self.assignment;
+        let level = |lit| assignment.level(lit).unwrap();
+
+        let lits = self.db.lits_mut(conflict);
+        let max = lits.iter().map(|&lit| level(lit)).max().unwrap();
+        let count = lits.iter().filter(|&&lit| level(lit) == max).count();
+        if level(lits[0]) == max && level(lits[1]) == max {
+            return (max, count);
+        }
+
+        let old = [lits[0], lits[1]];
+        for i in 0..2 {
+            let (j, _) = lits[i..]
+                .iter()
+                .enumerate()
+                .max_by_key(|&(_, &lit)| level(lit))
+                .unwrap();
+            lits.swap(i, i + j);
+        }
+
+        let new = [lits[0], lits[1]];
+        if lits.len() > 2 && watched {
+            for i in 0..2 {
+                if !new.contains(&old[i]) {
+                    let watched = &mut self.watched[old[i]];
+                    let pos = watched.iter().position(|w| w.clause == conflict);
+                    watched.swap_remove(pos.unwrap());
+                }
+                if !old.contains(&new[i]) {
+                    self.watched[new[i]].push(Watch::new(conflict, new[1 - i]));
+                }
+            }
+        }
+
+        (max, count)
+    }
+
+    fn lbd(&self, clause: &[Lit]) -> usize {
+        let mut levels: Vec<usize> = clause
+            .iter()
+            .map(|&lit| self.assignment.level(lit).unwrap())
+            .collect();
+        levels.sort_unstable();
+        levels.dedup();
+        levels.len()
+    }
+
+    fn choose(&mut self) -> Option<Var> {
+        if self.random_freq > 0.0 && self.rng.next_f64() < self.random_freq {
+            let var = self.evsids.choose_random(&self.assignment, &mut self.rng);
+            if var.is_some() {
+                return var;
+            }
+        }
+        self.evsids.choose(&self.assignment)
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        let evsids = &mut self.evsids;
+        let start = self.assignment.backtrack(level, |var| evsids.insert(var));
+        self.prop_head = std::cmp::min(self.prop_head, start);
+        self.binary_head = std::cmp::min(self.binary_head, start);
+        self.card_head = std::cmp::min(self.card_head, start);
+        self.gauss_head = std::cmp::min(self.gauss_head, start);
+    }
+
+    fn restart(&mut self) {
+        self.assignment.update_phases(self.assignment.trail().len());
+        self.assignment.reset_target();
+
+        // the levels with decisions more active than the next one
+        // would be made again in the same order, so they are kept
+        let last_level = self.assignment.last_level();
+        let reused = match self.evsids.choose(&self.assignment) {
+            Some(next) => {
+                let activity = self.evsids.activity(next);
+                (1..=last_level)
+                    .take_while(|&level| {
+                        let var = to_var(self.assignment.decision(level));
+                        self.evsids.activity(var) > activity
+                    })
+                    .count()
+            }
+            None => last_level,
+        };
+        if reused < last_level {
+            self.backtrack(reused + 1);
+        }
+    }
+
+    fn replay(&mut self, decisions: &[Lit]) {
+        for &lit in decisions {
+            if self.assignment.eval(lit).is_some() {
+                continue;
+            }
+            self.assignment.set(lit, Reason::Decision);
+            if self.propagate().is_some() {
+                // found again by the search
+                self.backtrack(self.assignment.last_level());
+                return;
+            }
+        }
+    }
+
+    fn rephase(&mut self) {
+        let kind = self.rephase_schedule.next().unwrap();
+        match kind {
+            Rephase::Walk => {
+                let mut phases = self.assignment.saved_phases().clone();
+                let assignment = &self.assignment;
+                // the clauses replaced by cardinality constraints take part
+                let covered = self.cards.as_ref().map_or(&[][..], |cards| &cards.covered);
+                walk::walk(
+                    self.clauses
+                        .iter()
+                        .map(|&c| self.db.lits(c))
+                        .chain(covered.iter().map(Vec::as_slice)),
+                    &mut phases,
+                    |var| match assignment.level(var as Lit) {
+                        Some(0) => assignment.eval(var as Lit),
+                        _ => None,
+                    },
+                    &mut self.rng,
+                    WALK_EFFORT * (self.clauses.len() + covered.len()),
+                );
+                self.assignment.set_phases(phases);
+            }
+            _ => self.assignment.rephase(kind, &mut self.rng),
+        }
+    }
+
     pub fn solve(&mut self) -> Solution {
-        for (i, clause) in self.clauses.iter().enumerate() {
-            match clause[..] {
+        for &c in &self.clauses {
+            match self.db.lits(c) {
                 [] => return Solution::Unsat,
-                [lit] => match self.assignment.eval(lit) {
-                    None => self
-                        .assignment
-                        .set(lit, Reason::Propagation { i_clause: i }),
+                &[lit] => match self.assignment.eval(lit) {
+                    None => self.assignment.set(lit, Reason::Propagation { clause: c }),
                     Some(false) => return Solution::Unsat,
                     Some(true) => (),
                 },
@@ -293,52 +1005,138 @@ impl Solver {
             }
         }
 
-        if let Some(_i_conflict) = self.propagate() {
+        if let Some(_conflict) = self.propagate() {
+            return Solution::Unsat;
+        }
+        self.simplify_root();
+        if !self.probe() {
+            if let Some(proof) = self.proof.as_mut() {
+                proof.push((ProofStep::Add, vec![]));
+            }
+            return Solution::Unsat;
+        }
+        // XOR reasoning is not covered by DRAT proofs
+        if self.proof.is_none() && !self.no_gauss && !self.enable_gauss() {
+            return Solution::Unsat;
+        }
+        // neither are cardinality constraints
+        if self.proof.is_none() && self.cardinality && !self.enable_cards() {
             return Solution::Unsat;
         }
 
-        while let Some(var) = self.evsids.choose(&self.assignment) {
+        while let Some(var) = self.choose() {
             self.assignment.decide(var);
 
-            while let Some(i_conflict) = self.propagate() {
+            while let Some(conflict) = self.propagate() {
+                let (conflict_level, count) = self.conflict_level(conflict);
+
+                if conflict_level == 0 {
+                    if let Some(proof) = self.proof.as_mut() {
+                        proof.push((ProofStep::Add, vec![]));
+                    }
+                    return Solution::Unsat;
+                }
+
+                if count == 1 {
+                    // an implication missed due to chronological backtracking,
+                    // the clause becomes the reason of its highest literal
+                    self.backtrack(conflict_level);
+                    let lit = self.db.lits(conflict)[0];
+                    let level = self.assignment.level(self.db.lits(conflict)[1]).unwrap();
+                    self.assignment.imply(lit, conflict, level);
+                    continue;
+                }
+
                 self.conflicts += 1;
+                self.rephase_conflicts += 1;
+                self.inprocess_conflicts += 1;
 
-                let (learnt, level) = self.analyze(i_conflict);
+                if conflict_level < self.assignment.last_level() {
+                    self.backtrack(conflict_level + 1);
+                }
+
+                let (learnt, mut level) = self.analyze(conflict);
+                let lbd = self.lbd(&learnt);
 
                 if let Some(proof) = self.proof.as_mut() {
                     proof.push((ProofStep::Add, learnt.clone()));
                 }
 
-                if level == 0 {
-                    return Solution::Unsat;
+                self.assignment
+                    .update_phases(self.assignment.consistent_len());
+                if self.assignment.last_level() + 1 - level > CHRONO_LEVELS {
+                    level = self.assignment.last_level();
                 }
-                self.assignment.backtrack(level);
-                self.prop_head = std::cmp::min(self.prop_head, self.assignment.trail().len());
+                self.backtrack(level);
 
                 let lit_assert = learnt[0];
-                let i_clause = self.add(learnt);
-                self.assignment
-                    .set(lit_assert, Reason::Propagation { i_clause });
+                let assert_level = match learnt.get(1) {
+                    Some(&lit) => self.assignment.level(lit).unwrap(),
+                    None => 0,
+                };
+                let c = self.add(&learnt, true);
+                self.db.set_lbd(c, lbd);
+                self.assignment.imply(lit_assert, c, assert_level);
             }
 
-            let learnt_count = self.clauses.len() - self.min_clause_count;
+            self.release_reasons();
+            self.simplify_root();
+
+            let learnt_count = self.learnts.len();
             let removable = learnt_count.saturating_sub(self.assignment.trail().len());
             if removable > self.max_learnt as usize {
                 self.prune();
                 self.max_learnt *= 1.001;
+                self.vivify_pending = true;
             }
 
             if self.conflicts >= *self.restart_threshold.peek().unwrap() {
                 self.conflicts = 0;
                 self.restart_threshold.next();
-                if self.assignment.last_level() >= 1 {
-                    self.assignment.backtrack(1);
-                    self.prop_head = std::cmp::min(self.prop_head, self.assignment.trail().len());
+                self.restart();
+
+                // vivification needs level 0, the reused decisions
+                // are made again afterwards
+                if self.vivify_pending {
+                    self.vivify_pending = false;
+                    let decisions: Vec<Lit> = (1..=self.assignment.last_level())
+                        .map(|level| self.assignment.decision(level))
+                        .collect();
+                    if !(self.restart_root() && self.vivify()) {
+                        if let Some(proof) = self.proof.as_mut() {
+                            proof.push((ProofStep::Add, vec![]));
+                        }
+                        return Solution::Unsat;
+                    }
+                    self.replay(&decisions);
+                }
+            }
+
+            if self.rephase_conflicts >= REPHASE_INTERVAL * self.rephase_count {
+                self.rephase_conflicts = 0;
+                self.rephase_count += 1;
+                self.rephase();
+            }
+
+            if self.inprocess_conflicts >= INPROCESS_INTERVAL * self.inprocess_count {
+                self.inprocess_conflicts = 0;
+                self.inprocess_count += 1;
+                if !self.inprocess() {
+                    if let Some(proof) = self.proof.as_mut() {
+                        proof.push((ProofStep::Add, vec![]));
+                    }
+                    return Solution::Unsat;
                 }
             }
         }
 
-        let model: Vec<Lit> = self.assignment.trail().to_vec();
+        let model: Vec<Lit> = self
+            .assignment
+            .trail()
+            .iter()
+            .copied()
+            .filter(|&lit| to_var(lit) <= self.original_var_count)
+            .collect();
         Solution::Sat { model }
     }
 
@@ -347,16 +1145,38 @@ impl Solver {
     }
 }
 
+fn abstract_level(level: usize) -> u64 {
+    1 << (level % 64)
+}
+
+fn is_reason(db: &ClauseDb, assignment: &Assignment, c: ClauseRef) -> bool {
+    // The implied literal is always stored first, except for binary
+    // clauses, which are propagated without touching the clause.
+    let lits = db.lits(c);
+    let implied = if lits.len() == 2 {
+        lits
+    } else {
+        &lits[..lits.len().min(1)]
+    };
+    implied.iter().any(|&lit| {
+        assignment.eval(lit) == Some(true)
+            && assignment.reason(lit) == Some(Reason::Propagation { clause: c })
+    })
+}
+
 pub fn verify(problem: &Problem, sat: bool, solution: &Solution) -> bool {
     match solution {
         Solution::Sat { model } => {
             if sat {
                 let mut sorted = model.to_vec();
                 sorted.sort();
-                problem
-                    .clauses
-                    .iter()
-                    .all(|clause| clause.iter().any(|lit| sorted.binary_search(lit).is_ok()))
+                let count = |lits: &Clause| {
+                    lits.iter()
+                        .filter(|lit| sorted.binary_search(lit).is_ok())
+                        .count()
+                };
+                problem.clauses.iter().all(|clause| count(clause) > 0)
+                    && problem.xors.iter().all(|xor| count(xor) % 2 == 1)
             } else {
                 false
             }
@@ -368,20 +1188,29 @@ pub fn verify(problem: &Problem, sat: bool, solution: &Solution) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use crate::types::{Clause, Problem};
+    use crate::types::{Clause, Lit, Problem};
 
-    use super::{verify, Solver};
+    use super::{
+        assignment::Reason, gauss::parity_clauses, verify, Options, Polarity, Solution, Solver,
+    };
 
-    fn check(clauses: Vec<Clause>, sat: bool) {
+    fn check_with(clauses: Vec<Clause>, sat: bool, options: &Options) {
         let problem = Problem {
-            var_count: clauses.iter().flatten().max().unwrap().abs() as usize,
+            var_count: clauses.iter().flatten().max().unwrap().unsigned_abs() as usize,
             clauses,
+            xors: vec![],
         };
 
-        let solution = Solver::new(problem.clone()).solve();
+        let mut solver = Solver::new(problem.clone());
+        solver.set_options(options);
+        let solution = solver.solve();
         assert!(verify(&problem, sat, &solution));
     }
 
+    fn check(clauses: Vec<Clause>, sat: bool) {
+        check_with(clauses, sat, &Options::default());
+    }
+
     #[test]
     /// Formulas from the lecture.
     fn basic_sat() {
@@ -414,10 +1243,453 @@ mod tests {
         check(clauses, false);
     }
 
+    #[test]
+    fn binary() {
+        let clauses = vec![
+            vec![-1, 2],
+            vec![-2, 3],
+            vec![-3, -1],
+            vec![1, 4],
+            vec![1, -4],
+        ];
+        check(clauses, false);
+
+        let clauses = vec![
+            vec![-1, 2],
+            vec![-2, 3],
+            vec![-3, -1],
+            vec![1, 4],
+            vec![-4, 5],
+        ];
+        check(clauses, true);
+    }
+
     #[test]
     /// Formulas with non-trivial propagation before the first decision.
     fn kickstart() {
         let clauses = vec![vec![1], vec![-1, 2], vec![-1, -2]];
         check(clauses, false);
     }
+
+    #[test]
+    fn root_simplification() {
+        let problem = Problem {
+            var_count: 5,
+            clauses: vec![vec![1], vec![-1, 2, 3], vec![1, 4, 5], vec![-2, 3, 4]],
+            xors: vec![],
+        };
+
+        let mut solver = Solver::with_proof(problem.clone());
+        let solution = solver.solve();
+        assert!(verify(&problem, true, &solution));
+
+        // the satisfied clauses are gone, the false literal is removed
+        let clauses: Vec<Clause> = solver
+            .clauses
+            .iter()
+            .map(|&c| {
+                let mut lits = solver.db.lits(c).to_vec();
+                lits.sort();
+                lits
+            })
+            .collect();
+        assert_eq!(clauses, [vec![2, 3], vec![-2, 3, 4]]);
+        assert!(solver.binary[2].iter().any(|watch| watch.blocker == 3));
+    }
+
+    #[test]
+    fn probing() {
+        let problem = Problem {
+            var_count: 19,
+            clauses: vec![
+                // 1 fails
+                vec![-1, 2],
+                vec![-1, 3],
+                vec![-2, -3, 4],
+                vec![-1, -4],
+                // 7 is implied by both 6 and -6
+                vec![-6, 7],
+                vec![6, 7],
+                // 8 implies 12 through 9 and a long clause
+                vec![-8, 9],
+                vec![-9, 10],
+                vec![-9, 11],
+                vec![-10, -11, 12],
+                // 13 implies 18 through 15, which then implies 19 together with 14
+                vec![-13, 14],
+                vec![-14, 15],
+                vec![-15, 16],
+                vec![-15, 17],
+                vec![-16, -17, 18],
+                vec![-14, -18, 19],
+            ],
+            xors: vec![],
+        };
+
+        let mut solver = Solver::with_proof(problem);
+        assert!(solver.probe());
+        assert_eq!(solver.assignment.eval(-1), Some(true));
+        assert_eq!(solver.assignment.eval(7), Some(true));
+        assert_eq!(solver.assignment.eval(8), None);
+        // the resolvent is on the dominator, not on the probe
+        let implies = |solver: &Solver, lit: Lit, other: Lit| {
+            solver.binary[-lit]
+                .iter()
+                .any(|watch| watch.blocker == other)
+        };
+        assert!(implies(&solver, 9, 12));
+        assert!(!implies(&solver, 8, 12));
+        // 18 hangs below 15 in the implication tree, so the dominator
+        // of the reason of 19 is 14, neither the probe nor 18's dominator
+        assert!(implies(&solver, 15, 18));
+        assert!(implies(&solver, 14, 19));
+        assert!(!implies(&solver, 13, 18));
+        assert!(!implies(&solver, 13, 19));
+        assert!(!implies(&solver, 15, 19));
+    }
+
+    #[test]
+    fn vivification() {
+        let problem = Problem {
+            var_count: 4,
+            clauses: vec![vec![-1, 2], vec![-2, 3]],
+            xors: vec![],
+        };
+
+        let mut solver = Solver::with_proof(problem);
+        let c = solver.add(&[-1, 4, 3], true);
+        assert!(solver.vivify());
+        // 1 implies 3
+        assert_eq!(solver.db.lits(c), &[-1, 3]);
+        assert!(solver.db.is_vivified(c));
+        assert!(solver.binary[-1].iter().any(|watch| watch.blocker == 3));
+        assert!(solver.watched[4].is_empty());
+    }
+
+    fn pigeon_clauses(pigeons: i32, holes: i32) -> Vec<Clause> {
+        let var = |pigeon: i32, hole: i32| pigeon * holes + hole + 1;
+        let mut clauses: Vec<Clause> = (0..pigeons)
+            .map(|pigeon| (0..holes).map(|hole| var(pigeon, hole)).collect())
+            .collect();
+        for hole in 0..holes {
+            for a in 0..pigeons {
+                for b in a + 1..pigeons {
+                    clauses.push(vec![-var(a, hole), -var(b, hole)]);
+                }
+            }
+        }
+        clauses
+    }
+
+    #[test]
+    fn cardinality() {
+        let options = Options {
+            cardinality: true,
+            ..Default::default()
+        };
+        check_with(pigeon_clauses(6, 5), false, &options);
+
+        // at most two of 26..=30 in the binomial encoding, at least two
+        let mut clauses = pigeon_clauses(5, 5);
+        for a in 26..=30 {
+            for b in a + 1..=30 {
+                for c in b + 1..=30 {
+                    clauses.push(vec![-a, -b, -c]);
+                }
+            }
+        }
+        clauses.extend([vec![26, 27], vec![29, 30]]);
+        let problem = Problem {
+            var_count: 30,
+            clauses,
+            xors: vec![],
+        };
+        let mut solver = Solver::new(problem.clone());
+        solver.set_options(&options);
+        let solution = solver.solve();
+        assert!(solver.cards.is_some());
+        assert!(verify(&problem, true, &solution));
+
+        // at most one of 1..=5 in the pairwise encoding, at least one
+        let mut clauses = vec![(1..=5).collect()];
+        for a in 1..=5 {
+            for b in a + 1..=5 {
+                clauses.push(vec![-a, -b]);
+            }
+        }
+        let problem = Problem {
+            var_count: 5,
+            clauses,
+            xors: vec![],
+        };
+        let mut solver = Solver::new(problem.clone());
+        assert!(solver.enable_cards());
+        assert!((1..=5).all(|var| solver.binary[var].is_empty() && solver.binary[-var].is_empty()));
+        assert_eq!(solver.clauses.len(), 1);
+
+        solver.assignment.set(1, Reason::Decision);
+        assert!(solver.propagate().is_none());
+        for lit in -5..=-2 {
+            assert_eq!(solver.assignment.eval(lit), Some(true));
+            let Some(Reason::Propagation { clause }) = solver.assignment.reason(lit) else {
+                panic!("expected a reason");
+            };
+            assert!(solver.db.is_temporary(clause));
+        }
+
+        let mut solver = Solver::new(problem);
+        assert!(solver.enable_cards());
+        solver.assignment.set(1, Reason::Decision);
+        solver.assignment.set(2, Reason::Decision);
+        let conflict = solver.propagate().unwrap();
+        assert!(solver.db.is_temporary(conflict));
+        assert_eq!(solver.analyze(conflict).0, vec![-2, -1]);
+    }
+
+    #[test]
+    fn xor() {
+        // a chain of XORs
+        let clauses: Vec<Clause> = (0..20)
+            .flat_map(|i| parity_clauses(&[2 * i + 1, 2 * i + 2, 2 * i + 3], i % 3 == 0))
+            .collect();
+        let problem = Problem {
+            var_count: 41,
+            clauses,
+            xors: vec![],
+        };
+        let mut solver = Solver::new(problem.clone());
+        let solution = solver.solve();
+        assert!(solver.gauss.is_some());
+        assert!(verify(&problem, true, &solution));
+
+        let mut solver = Solver::new(problem.clone());
+        solver.set_options(&Options {
+            no_gauss: true,
+            ..Options::default()
+        });
+        let solution = solver.solve();
+        assert!(solver.gauss.is_none());
+        assert!(verify(&problem, true, &solution));
+
+        // a ring of XORs sharing 21, their sum is 0 = 1
+        let clauses: Vec<Clause> = (0..20)
+            .flat_map(|i| parity_clauses(&[i + 1, (i + 1) % 20 + 1, 21], i == 0))
+            .collect();
+        check(clauses, false);
+    }
+
+    #[test]
+    fn xor_input() {
+        let problem = Problem {
+            var_count: 30,
+            clauses: vec![vec![1], vec![-2]],
+            xors: vec![(1..=30).collect(), (1..=15).map(|var| -var).collect()],
+        };
+        let mut solver = Solver::new(problem.clone());
+        let Solution::Sat { model } = solver.solve() else {
+            panic!("expected a model");
+        };
+        // the variables encoding the XORs are left out
+        assert_eq!(model.len(), 30);
+        assert!(verify(
+            &problem,
+            true,
+            &Solution::Sat {
+                model: model.clone()
+            }
+        ));
+        // flipping a variable only in the first XOR breaks its parity
+        let model = model
+            .iter()
+            .map(|&lit| if lit.abs() == 30 { -lit } else { lit });
+        assert!(!verify(
+            &problem,
+            true,
+            &Solution::Sat {
+                model: model.collect()
+            }
+        ));
+
+        // 1 and -1 cannot both make the XOR odd
+        let problem = Problem {
+            var_count: 30,
+            clauses: vec![],
+            xors: vec![(1..=30).collect(), [-1].into_iter().chain(2..=30).collect()],
+        };
+        let mut solver = Solver::new(problem);
+        assert!(matches!(solver.solve(), Solution::Unsat));
+    }
+
+    #[test]
+    #[should_panic(expected = "proofs cannot refer to XOR constraints")]
+    fn xor_proof() {
+        let problem = Problem {
+            var_count: 2,
+            clauses: vec![],
+            xors: vec![vec![1, 2]],
+        };
+        Solver::with_proof(problem);
+    }
+
+    #[test]
+    fn randomized() {
+        let clauses = vec![
+            vec![-1, -2, 3],
+            vec![2, -1, 3],
+            vec![1, -2, 3],
+            vec![-3, 4, 5],
+            vec![-3, 4, -5],
+            vec![-3, -4, 5],
+            vec![-3, -4, -5],
+        ];
+
+        for seed in 0..10 {
+            let options = Options {
+                seed: Some(seed),
+                random_freq: 0.5,
+                ..Default::default()
+            };
+            check_with(clauses.clone(), true, &options);
+        }
+    }
+
+    #[test]
+    fn phases() {
+        let problem = Problem {
+            var_count: 4,
+            clauses: vec![vec![1, 2, 3], vec![-3, 4]],
+            xors: vec![],
+        };
+
+        let mut solver = Solver::new(problem.clone());
+        solver.set_initial_polarity(Polarity::Positive);
+        solver.set_phase(-2);
+        let Solution::Sat { mut model } = solver.solve() else {
+            panic!("expected a model");
+        };
+        model.sort_by_key(|lit| lit.abs());
+        assert_eq!(model, vec![1, -2, 3, 4]);
+
+        // the seed leaves the phases set by the user alone,
+        // no matter the order of the calls
+        let options = Options {
+            seed: Some(7),
+            ..Default::default()
+        };
+        for seed_first in [false, true] {
+            let mut solver = Solver::new(problem.clone());
+            if seed_first {
+                solver.set_options(&options);
+            }
+            solver.set_initial_polarity(Polarity::Positive);
+            solver.set_phase(-2);
+            if !seed_first {
+                solver.set_options(&options);
+            }
+            let Solution::Sat { mut model } = solver.solve() else {
+                panic!("expected a model");
+            };
+            model.sort_by_key(|lit| lit.abs());
+            assert_eq!(model, vec![1, -2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn decision_vars() {
+        // 2 <-> 3 <-> 4
+        let problem = Problem {
+            var_count: 4,
+            clauses: vec![
+                vec![-2, 3],
+                vec![2, -3],
+                vec![-3, 4],
+                vec![3, -4],
+                vec![1, 4],
+            ],
+            xors: vec![],
+        };
+
+        let mut solver = Solver::new(problem.clone());
+        for var in [1, 3, 4] {
+            solver.set_decision_var(var, false);
+        }
+        let solution = solver.solve();
+        let Solution::Sat { model } = &solution else {
+            panic!("expected a model");
+        };
+        // the only decision is on 2, the rest follows by propagation
+        assert_eq!(model[0].abs(), 2);
+        assert!(verify(&problem, true, &solution));
+    }
+
+    fn learn(clauses: Vec<Clause>, decisions: &[Lit]) -> (Clause, usize) {
+        let problem = Problem {
+            var_count: clauses
+                .iter()
+                .flatten()
+                .map(|lit| lit.unsigned_abs())
+                .max()
+                .unwrap() as usize,
+            clauses,
+            xors: vec![],
+        };
+        let mut solver = Solver::new(problem);
+
+        let (&last, decisions) = decisions.split_last().unwrap();
+        for &lit in decisions {
+            solver.assignment.set(lit, Reason::Decision);
+            assert!(solver.propagate().is_none());
+        }
+        solver.assignment.set(last, Reason::Decision);
+        let conflict = solver.propagate().unwrap();
+        let (mut learnt, level) = solver.analyze(conflict);
+        learnt[1..].sort();
+        (learnt, level)
+    }
+
+    #[test]
+    fn analysis() {
+        let clauses = vec![vec![-1, -2, 3], vec![-2, -3, 4], vec![-1, -3, -4]];
+        // -1 and -3 are reached twice, 2 is the first UIP
+        assert_eq!(learn(clauses.clone(), &[1, 2]), (vec![-2, -1], 2));
+        check(clauses, true);
+    }
+
+    #[test]
+    fn minimization() {
+        let clauses = vec![vec![-1, 2], vec![-3, -1, 4], vec![-3, -2, -4]];
+        // -2 is implied by -1
+        assert_eq!(learn(clauses.clone(), &[1, 3]), (vec![-3, -1], 2));
+        check(clauses, true);
+    }
+
+    #[test]
+    fn shrinking() {
+        let clauses = vec![vec![-1, 2], vec![-1, 3], vec![-4, -2, 5], vec![-4, -3, -5]];
+        // minimization fails as 1 is a decision,
+        // but -2 and -3 are replaced by their block UIP
+        assert_eq!(learn(clauses.clone(), &[1, 4]), (vec![-4, -1], 2));
+        check(clauses, true);
+    }
+
+    #[test]
+    fn trail_reuse() {
+        let problem = Problem {
+            var_count: 4,
+            clauses: vec![vec![1, 2, 3, 4]],
+            xors: vec![],
+        };
+
+        // the next decision would be on 4, so the levels up to the first
+        // decision less active than 4 are kept
+        for (decisions, kept) in [(vec![1, 3], 2), (vec![1, 2, 3], 1)] {
+            let mut solver = Solver::new(problem.clone());
+            solver.seed_activity([(1, 4.0), (2, 1.0), (3, 3.0), (4, 2.0)]);
+            for &lit in &decisions {
+                solver.assignment.set(lit, Reason::Decision);
+            }
+            solver.restart();
+            assert_eq!(solver.assignment.trail(), &decisions[..kept]);
+        }
+    }
 }
@@ -0,0 +1,179 @@
+use crate::types::{Lit, ProofStep};
+
+use super::{assignment::Reason, map::LitMap, Solver, GLUE_LBD};
+
+/// The number of literals propagated while probing, per original clause.
+const PROBE_EFFORT: usize = 10;
+/// Hyper-binary resolvents are pruned like learnt clauses
+/// unless they take part in conflicts.
+const HBR_LBD: usize = GLUE_LBD + 1;
+
+impl Solver {
+    /// Failed literal probing. Both literals of the variables occurring
+    /// in binary clauses are assigned at level 1 and propagated:
+    /// - if a literal leads to a conflict, its negation is a fact,
+    /// - literals implied by both are facts (necessary assignments),
+    /// - literals implied by long clauses get a binary clause with the
+    ///   dominator of their reason (hyper-binary resolution), so they are
+    ///   found faster next time.
+    ///
+    /// Continues where the last call stopped until the effort is exhausted.
+    pub(super) fn probe(&mut self) -> bool {
+        let var_count = self.assignment.var_count();
+        let budget = PROBE_EFFORT * self.clauses.len();
+        let mut effort = 0;
+        let mut marks = LitMap::<bool>::new(var_count);
+        let phases = self.assignment.saved_phases().clone();
+
+        for _ in 0..var_count {
+            if effort > budget {
+                break;
+            }
+            let var = self.probe_next;
+            self.probe_next = var % var_count + 1;
+
+            let lit = var as Lit;
+            if self.assignment.eval(lit).is_some()
+                || (self.binary[lit].is_empty() && self.binary[-lit].is_empty())
+            {
+                continue;
+            }
+
+            let Some(pos) = self.probe_lit(lit, &mut effort) else {
+                if !self.add_fact(-lit) {
+                    return false;
+                }
+                continue;
+            };
+            let Some(neg) = self.probe_lit(-lit, &mut effort) else {
+                if !self.add_fact(lit) {
+                    return false;
+                }
+                continue;
+            };
+
+            for &other in &pos {
+                marks[other] = true;
+            }
+            let necessary: Vec<Lit> = neg.into_iter().filter(|&other| marks[other]).collect();
+            for &other in &pos {
+                marks[other] = false;
+            }
+
+            for other in necessary {
+                if self.assignment.eval(other).is_some() {
+                    continue;
+                }
+                // the fact follows from both implications
+                let implications = [vec![other, -lit], vec![other, lit]];
+                if let Some(proof) = self.proof.as_mut() {
+                    for clause in &implications {
+                        proof.push((ProofStep::Add, clause.clone()));
+                    }
+                }
+                if !self.add_fact(other) {
+                    return false;
+                }
+                if let Some(proof) = self.proof.as_mut() {
+                    for clause in implications {
+                        proof.push((ProofStep::Delete, clause));
+                    }
+                }
+            }
+        }
+
+        self.assignment.restore_saved_phases(phases);
+        self.simplify_root();
+        true
+    }
+
+    /// Assigns `lit` at level 1 and returns the implied literals,
+    /// or `None` if there is a conflict. Backtracks to level 0 afterwards.
+    /// The propagated literals are added to the `effort`.
+    fn probe_lit(&mut self, lit: Lit, effort: &mut usize) -> Option<Vec<Lit>> {
+        debug_assert_eq!(self.assignment.last_level(), 0);
+        let start = self.assignment.trail().len();
+        self.assignment.set(lit, Reason::Decision);
+        let conflict = self.propagate();
+
+        let implied = self.assignment.trail()[start + 1..].to_vec();
+        *effort += 1 + implied.len();
+        if conflict.is_some() {
+            self.backtrack(1);
+            return None;
+        }
+        let resolvents = self.hyper_binary_resolvents(start);
+        self.backtrack(1);
+
+        for resolvent in resolvents {
+            if let Some(proof) = self.proof.as_mut() {
+                proof.push((ProofStep::Add, resolvent.to_vec()));
+            }
+            let c = self.add(&resolvent, true);
+            self.db.set_lbd(c, HBR_LBD);
+        }
+        Some(implied)
+    }
+
+    /// The binary clauses `-dom ∨ lit` for the literals implied by long
+    /// clauses since the probe at trail position `start`, where `dom` is
+    /// the closest dominator of the other literals of the reason in the
+    /// implication tree. Literals implied by binary clauses are already
+    /// reachable through binary clauses and get none.
+    fn hyper_binary_resolvents(&self, start: usize) -> Vec<[Lit; 2]> {
+        let trail = &self.assignment.trail()[start..];
+        // the parent of each literal in the implication tree rooted at the
+        // probe, as trail positions relative to `start`, literals implied
+        // by long clauses hang below their dominator
+        let mut parents = vec![0; trail.len()];
+        let mut resolvents = vec![];
+
+        for (i, &lit) in trail.iter().enumerate().skip(1) {
+            if self.assignment.level(lit) != Some(1) {
+                continue;
+            }
+            let Some(Reason::Propagation { clause }) = self.assignment.reason(lit) else {
+                continue;
+            };
+            let lits = self.db.lits(clause);
+            let dom = lits
+                .iter()
+                .filter(|&&other| other != lit && self.assignment.level(other) == Some(1))
+                .map(|&other| self.assignment.position(other).unwrap() - start)
+                .reduce(|a, b| dominator(&parents, a, b))
+                .unwrap_or(0);
+            parents[i] = dom;
+            if lits.len() > 2 {
+                resolvents.push([lit, -trail[dom]]);
+            }
+        }
+        resolvents
+    }
+
+    /// Adds the unit clause `lit` implied by the current clauses
+    /// and propagates it at level 0. Returns `false` on conflict.
+    pub(super) fn add_fact(&mut self, lit: Lit) -> bool {
+        if let Some(value) = self.assignment.eval(lit) {
+            return value;
+        }
+        if let Some(proof) = self.proof.as_mut() {
+            proof.push((ProofStep::Add, vec![lit]));
+        }
+        let c = self.add(&[lit], true);
+        self.assignment.set(lit, Reason::Propagation { clause: c });
+        self.propagate().is_none()
+    }
+}
+
+/// The closest common ancestor of the trail positions `a` and `b`
+/// in the implication tree given by the `parents`.
+fn dominator(parents: &[usize], mut a: usize, mut b: usize) -> usize {
+    while a != b {
+        if a > b {
+            a = parents[a];
+        } else {
+            b = parents[b];
+        }
+    }
+    a
+}
@@ -0,0 +1,61 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rephase {
+    /// Reset the saved phases to the initial ones
+    /// (see [`Solver::set_phase`](super::Solver::set_phase)).
+    Original,
+    /// Flip the initial phases.
+    Inverted,
+    /// Use the phases of the largest conflict-free trail seen so far.
+    Best,
+    /// Pick the phases at random.
+    Random,
+    /// Use the best assignment found by local search.
+    Walk,
+}
+
+/// The rephasing schedule, loosely based on Kissat's.
+/// After trying the original and inverted phases once,
+/// it cycles through the remaining ones, reverting to the best phases
+/// in between.
+pub struct Schedule {
+    count: usize,
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self { count: 0 }
+    }
+}
+
+impl Iterator for Schedule {
+    type Item = Rephase;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        use Rephase::*;
+
+        const CYCLE: [Rephase; 8] = [Best, Walk, Best, Original, Best, Inverted, Best, Random];
+
+        let kind = match self.count {
+            0 => Original,
+            1 => Inverted,
+            i => CYCLE[(i - 2) % CYCLE.len()],
+        };
+        self.count += 1;
+        Some(kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rephase::*, Schedule};
+
+    #[test]
+    fn basic() {
+        let expected = vec![
+            Original, Inverted, Best, Walk, Best, Original, Best, Inverted, Best, Random, Best,
+            Walk,
+        ];
+        let actual: Vec<_> = Schedule::new().take(12).collect();
+        assert_eq!(expected, actual);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::types::{Clause, Lit};
+
+use super::map::LitMap;
+
+/// Clauses are not checked against literals with more occurrences.
+const OCCURRENCE_LIMIT: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    /// The clause is subsumed by another one and can be removed.
+    Remove(usize),
+    /// The literal can be removed from the clause (self-subsuming resolution).
+    Strengthen(usize, Lit),
+}
+
+/// Finds the clauses subsumed by others and the literals that can be removed
+/// by self-subsuming resolution. The returned actions refer to the indices
+/// in `clauses` and need to be applied in order, strengthened clauses are
+/// checked again.
+/// A `redundant` (learnt) clause never removes an irredundant one, since
+/// it might be deleted later.
+pub fn subsume(var_count: usize, clauses: &[&[Lit]], redundant: &[bool]) -> Vec<Action> {
+    let mut clauses: Vec<Clause> = clauses.iter().map(|clause| clause.to_vec()).collect();
+    let mut removed = vec![false; clauses.len()];
+
+    let mut occurs = LitMap::<Vec<usize>>::new(var_count);
+    for (i, clause) in clauses.iter().enumerate() {
+        for &lit in clause {
+            occurs[lit].push(i);
+        }
+    }
+
+    // shorter clauses are more likely to subsume others, so they go first
+    let mut queue: Vec<usize> = (0..clauses.len()).collect();
+    queue.sort_unstable_by_key(|&i| std::cmp::Reverse(clauses[i].len()));
+    let mut queued = vec![true; clauses.len()];
+
+    let mut marks = LitMap::<bool>::new(var_count);
+    let mut actions = vec![];
+
+    while let Some(i) = queue.pop() {
+        queued[i] = false;
+        if removed[i] || clauses[i].is_empty() {
+            continue;
+        }
+
+        // all candidates contain the variable with the fewest occurrences
+        let clause = clauses[i].clone();
+        let &best = clause
+            .iter()
+            .min_by_key(|&&lit| occurs[lit].len() + occurs[-lit].len())
+            .unwrap();
+        if occurs[best].len() + occurs[-best].len() > OCCURRENCE_LIMIT {
+            continue;
+        }
+        let candidates: Vec<usize> = occurs[best].iter().chain(&occurs[-best]).copied().collect();
+
+        for &lit in &clause {
+            marks[lit] = true;
+        }
+
+        for j in candidates {
+            if j == i || removed[j] || clauses[j].len() < clause.len() {
+                continue;
+            }
+
+            match check(&clause, &clauses[j], &marks) {
+                Check::None => (),
+                Check::Subsumed => {
+                    if redundant[i] && !redundant[j] {
+                        continue;
+                    }
+                    removed[j] = true;
+                    actions.push(Action::Remove(j));
+                }
+                Check::Strengthen(lit) => {
+                    clauses[j].retain(|&other| other != lit);
+                    occurs[lit].retain(|&k| k != j);
+                    actions.push(Action::Strengthen(j, lit));
+                    if !queued[j] {
+                        queued[j] = true;
+                        queue.push(j);
+                    }
+                }
+            }
+        }
+
+        for &lit in &clause {
+            marks[lit] = false;
+        }
+    }
+
+    actions
+}
+
+enum Check {
+    None,
+    Subsumed,
+    /// The literal can be removed by resolving with the subsuming clause.
+    Strengthen(Lit),
+}
+
+/// Checks whether `clause` (whose literals are `marked`) subsumes `other`,
+/// possibly after flipping a single literal.
+fn check(clause: &[Lit], other: &[Lit], marks: &LitMap<bool>) -> Check {
+    let mut found = 0;
+    let mut flipped = None;
+    for &lit in other {
+        if marks[lit] {
+            found += 1;
+        } else if marks[-lit] {
+            if flipped.is_some() {
+                return Check::None;
+            }
+            flipped = Some(lit);
+        }
+    }
+
+    match flipped {
+        None if found == clause.len() => Check::Subsumed,
+        Some(lit) if found + 1 == clause.len() => Check::Strengthen(lit),
+        _ => Check::None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{subsume, Action};
+
+    #[test]
+    fn basic() {
+        let clauses: [&[i32]; 3] = [&[1, 2, 3], &[1, 2], &[-1, 2, 4]];
+        let actions = subsume(4, &clauses, &[false; 3]);
+        assert_eq!(actions, vec![Action::Remove(0), Action::Strengthen(2, -1)]);
+    }
+
+    #[test]
+    fn redundant() {
+        let clauses: [&[i32]; 3] = [&[1, 2, 3], &[1, 2], &[-1, 2, 3]];
+        let actions = subsume(3, &clauses, &[false, true, false]);
+        // the learnt clause cannot remove the first one,
+        // but the strengthened third one can
+        assert_eq!(actions, vec![Action::Strengthen(2, -1), Action::Remove(0)]);
+    }
+}
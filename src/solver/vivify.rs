@@ -0,0 +1,95 @@
+use crate::types::{Clause, Lit};
+
+use super::{assignment::Reason, clause::ClauseRef, Solver};
+
+/// The number of literals propagated while vivifying, per learnt clause.
+const VIVIFY_EFFORT: usize = 20;
+
+impl Solver {
+    /// Vivifies the learnt clauses that were not vivified yet, the newest
+    /// first. The negations of the literals of a clause are assigned one by
+    /// one and propagated. Literals implied false are dropped, and the rest
+    /// of the clause is dropped once a literal is implied true
+    /// or a conflict is found.
+    pub(super) fn vivify(&mut self) -> bool {
+        let budget = VIVIFY_EFFORT * self.learnts.len();
+        let mut effort = 0;
+        let candidates: Vec<ClauseRef> = self
+            .learnts
+            .iter()
+            .rev()
+            .copied()
+            .filter(|&c| !self.db.is_vivified(c) && self.db.len(c) > 2)
+            .collect();
+        let phases = self.assignment.saved_phases().clone();
+
+        for c in candidates {
+            if effort > budget {
+                break;
+            }
+            self.db.set_vivified(c);
+
+            let lits = self.db.lits(c).to_vec();
+            // new facts might have been found by the previous clauses
+            if lits
+                .iter()
+                .any(|&lit| self.assignment.eval(lit) == Some(true))
+            {
+                continue;
+            }
+
+            let start = self.assignment.trail().len();
+            let mut kept: Clause = vec![];
+            for (i, &lit) in lits.iter().enumerate() {
+                let rest = &lits[i..];
+                if let Some(&implied) = rest
+                    .iter()
+                    .find(|&&other| self.assignment.eval(other) == Some(true))
+                {
+                    kept.push(implied);
+                    break;
+                }
+                if self.assignment.eval(lit).is_none() {
+                    kept.push(lit);
+                    self.assignment.set(-lit, Reason::Decision);
+                    if self.propagate().is_some() {
+                        break;
+                    }
+                }
+            }
+            effort += self.assignment.trail().len() - start;
+            self.backtrack(1);
+
+            if kept.len() < lits.len() && !self.shorten(c, &kept) {
+                return false;
+            }
+        }
+
+        self.assignment.restore_saved_phases(phases);
+        self.simplify_root();
+        true
+    }
+
+    /// Replaces the literals of the long clause `c` by the implied subset
+    /// `lits`, which contains no false literals, and updates the watches.
+    /// Returns `false` on conflict.
+    fn shorten(&mut self, c: ClauseRef, lits: &[Lit]) -> bool {
+        for &lit in &self.db.lits(c)[..2] {
+            self.watched[lit].retain(|watch| watch.clause != c);
+        }
+        self.strengthen(c, lits);
+
+        match *lits {
+            [lit] => {
+                if self.assignment.eval(lit).is_none() {
+                    self.assignment.set(lit, Reason::Propagation { clause: c });
+                }
+                self.propagate().is_none()
+            }
+            _ => {
+                self.watch(c);
+                true
+            }
+        }
+    }
+}
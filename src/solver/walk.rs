@@ -0,0 +1,216 @@
+use crate::types::{to_var, Lit, Var};
+
+use super::{
+    map::{LitMap, VarMap},
+    random::Rng,
+};
+
+/// ProbSAT-style local search used to derive phases for rephasing.
+struct Walker<'a> {
+    clauses: Vec<&'a [Lit]>,
+    occurs: LitMap<Vec<usize>>,
+    true_count: Vec<usize>,
+    unsat: Vec<usize>,
+    unsat_pos: Vec<usize>,
+    values: VarMap<bool>,
+}
+
+impl<'a> Walker<'a> {
+    fn new(var_count: usize, clauses: Vec<&'a [Lit]>, values: VarMap<bool>) -> Self {
+        let mut occurs = LitMap::<Vec<usize>>::new(var_count);
+        for (i, clause) in clauses.iter().enumerate() {
+            for &lit in clause.iter() {
+                occurs[lit].push(i);
+            }
+        }
+
+        let mut walker = Self {
+            true_count: vec![0; clauses.len()],
+            unsat: vec![],
+            unsat_pos: vec![0; clauses.len()],
+            clauses,
+            occurs,
+            values,
+        };
+
+        for i in 0..walker.clauses.len() {
+            let count = walker.clauses[i]
+                .iter()
+                .filter(|&&lit| walker.is_true(lit))
+                .count();
+            walker.true_count[i] = count;
+            if count == 0 {
+                walker.push_unsat(i);
+            }
+        }
+
+        walker
+    }
+
+    fn is_true(&self, lit: Lit) -> bool {
+        self.values[to_var(lit)] == lit.is_positive()
+    }
+
+    fn push_unsat(&mut self, i: usize) {
+        self.unsat_pos[i] = self.unsat.len();
+        self.unsat.push(i);
+    }
+
+    fn remove_unsat(&mut self, i: usize) {
+        let pos = self.unsat_pos[i];
+        let last = *self.unsat.last().unwrap();
+        self.unsat_pos[last] = pos;
+        self.unsat.swap_remove(pos);
+    }
+
+    /// The number of clauses that become unsatisfied by flipping `var`.
+    fn break_count(&self, var: Var) -> usize {
+        let lvar = var as Lit;
+        let lit = if self.values[var] { lvar } else { -lvar };
+        self.occurs[lit]
+            .iter()
+            .filter(|&&i| self.true_count[i] == 1)
+            .count()
+    }
+
+    fn flip(&mut self, var: Var) {
+        let lvar = var as Lit;
+        let lit = if self.values[var] { lvar } else { -lvar };
+        self.values[var] = !self.values[var];
+
+        for k in 0..self.occurs[lit].len() {
+            let i = self.occurs[lit][k];
+            self.true_count[i] -= 1;
+            if self.true_count[i] == 0 {
+                self.push_unsat(i);
+            }
+        }
+        for k in 0..self.occurs[-lit].len() {
+            let i = self.occurs[-lit][k];
+            self.true_count[i] += 1;
+            if self.true_count[i] == 1 {
+                self.remove_unsat(i);
+            }
+        }
+    }
+}
+
+/// Runs at most `flips` steps of local search on `clauses`
+/// starting from `phases` and stores the best assignment found back in `phases`.
+/// Variables with `fixed` values are never flipped.
+/// Returns the number of clauses falsified by the resulting assignment.
+pub fn walk<'a>(
+    clauses: impl Iterator<Item = &'a [Lit]>,
+    phases: &mut VarMap<bool>,
+    fixed: impl Fn(Var) -> Option<bool>,
+    rng: &mut Rng,
+    flips: usize,
+) -> usize {
+    let var_count = phases.len() - 1;
+
+    let mut values = phases.clone();
+    for (var, value) in values.iter_mut().enumerate().skip(1) {
+        if let Some(fixed_value) = fixed(var) {
+            *value = fixed_value;
+        }
+    }
+
+    let clauses: Vec<&[Lit]> = clauses
+        .filter(|clause| clause.iter().any(|&lit| fixed(to_var(lit)).is_none()))
+        .collect();
+
+    let mut walker = Walker::new(var_count, clauses, values);
+    let mut best = walker.unsat.len();
+    phases.clone_from(&walker.values);
+
+    // break values for 3-SAT from the ProbSAT paper
+    const CB: f64 = 2.06;
+    const EPS: f64 = 0.9;
+    let mut weights: Vec<(Var, f64)> = vec![];
+
+    for _ in 0..flips {
+        if walker.unsat.is_empty() {
+            break;
+        }
+
+        let i = walker.unsat[rng.below(walker.unsat.len())];
+        weights.clear();
+        for &lit in walker.clauses[i] {
+            let var = to_var(lit);
+            if fixed(var).is_none() {
+                let breaks = walker.break_count(var) as f64;
+                weights.push((var, (EPS + breaks).powf(-CB)));
+            }
+        }
+
+        let total: f64 = weights.iter().map(|(_, weight)| weight).sum();
+        let mut threshold = rng.next_f64() * total;
+        let mut chosen = weights.last().unwrap().0;
+        for &(var, weight) in &weights {
+            if threshold < weight {
+                chosen = var;
+                break;
+            }
+            threshold -= weight;
+        }
+
+        walker.flip(chosen);
+
+        if walker.unsat.len() < best {
+            best = walker.unsat.len();
+            phases.clone_from(&walker.values);
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        solver::{map::var_map, random::Rng},
+        types::Lit,
+    };
+
+    use super::walk;
+
+    #[test]
+    fn basic() {
+        let clauses: [Vec<Lit>; 6] = [
+            vec![1, 2, 3],
+            vec![-1, 2],
+            vec![-2, 3],
+            vec![-3, -1],
+            vec![-3, 4],
+            vec![-4, -5],
+        ];
+        let mut phases = var_map(5);
+        let unsat = walk(
+            clauses.iter().map(Vec::as_slice),
+            &mut phases,
+            |_| None,
+            &mut Rng::new(0),
+            1000,
+        );
+        assert_eq!(unsat, 0);
+        assert!(clauses.iter().all(|clause| clause
+            .iter()
+            .any(|&lit| phases[lit.unsigned_abs() as usize] == (lit > 0))));
+    }
+
+    #[test]
+    fn fixed() {
+        let clauses: [Vec<Lit>; 3] = [vec![1, 2], vec![-1, 2], vec![-2, 3]];
+        let mut phases = var_map(3);
+        let fixed = |var| if var == 3 { Some(false) } else { None };
+        let unsat = walk(
+            clauses.iter().map(Vec::as_slice),
+            &mut phases,
+            fixed,
+            &mut Rng::new(0),
+            100,
+        );
+        assert_eq!(unsat, 1);
+        assert!(!phases[3]);
+    }
+}
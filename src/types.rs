@@ -15,6 +15,9 @@ pub type Clause = Vec<Lit>;
 pub struct Problem {
     pub var_count: usize,
     pub clauses: Vec<Clause>,
+    /// XOR constraints, i.e., an odd number of the literals
+    /// of each must be true.
+    pub xors: Vec<Clause>,
 }
 
 pub enum Solution {
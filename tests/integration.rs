@@ -2,7 +2,9 @@ use std::{fs, path::Path};
 
 use vw_passat::{
     io::{self, drat},
-    parallel, solver,
+    parallel,
+    preprocess::Preprocessor,
+    solver,
     types::Proof,
 };
 
@@ -34,6 +36,7 @@ fn verify_proof(input: impl AsRef<Path>, sat: bool, proof: &Proof, format: drat:
 enum Mode {
     Serial,
     Parallel,
+    Preprocessed,
     Prover,
 }
 
@@ -45,7 +48,13 @@ fn test_dir(path: &str, sat: bool, mode: Mode) {
         let problem = io::read_problem(&mut file);
         let solution = match mode {
             Mode::Serial => solver::Solver::new(problem.clone()).solve(),
-            Mode::Parallel => parallel::solve(problem.clone(), None),
+            Mode::Parallel => parallel::solve(problem.clone(), None, &Default::default()),
+            Mode::Preprocessed => {
+                let mut preprocessor = Preprocessor::new(problem.clone());
+                preprocessor.eliminate();
+                let solution = solver::Solver::new(preprocessor.problem()).solve();
+                preprocessor.reconstruct(solution)
+            }
             Mode::Prover => {
                 let mut solver = solver::Solver::with_proof(problem.clone());
                 let solution = solver.solve();
@@ -82,6 +91,16 @@ fn unsat_uniform_v100_c430() {
     test_dir("tests/data/uuf100-430", false, Mode::Parallel);
 }
 
+#[test]
+fn preprocess_sat_uniform_v75_c325() {
+    test_dir("tests/data/uf75-325", true, Mode::Preprocessed);
+}
+
+#[test]
+fn preprocess_unsat_uniform_v75_c325() {
+    test_dir("tests/data/uuf75-325", false, Mode::Preprocessed);
+}
+
 #[test]
 #[ignore = "requires drat-trim and more time"]
 fn prove_sat_uniform_v125_c538() {